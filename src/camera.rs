@@ -1,7 +1,6 @@
 use bevy::{math::f32, prelude::*, window::PrimaryWindow};
-use bevy_ecs_ldtk::prelude::*;
 
-use crate::player::Player;
+use crate::{ldtk::LevelWalls, player::Player, turn::Actor, CAMERA_SCALE, GRID_SIZE};
 
 pub struct CameraPlugin<S: States> {
     pub state: S,
@@ -15,57 +14,62 @@ impl<S: States> Plugin for CameraPlugin<S> {
 #[derive(Component)]
 pub struct MainCamera;
 
+// room kept around the players when the camera zooms out to frame all of them
+const FRAME_MARGIN: f32 = (4 * GRID_SIZE) as f32;
+
 #[allow(clippy::type_complexity)]
 fn update_camera(
-    mut camera: Query<(&mut Transform, &OrthographicProjection), (With<Camera2d>, Without<Player>)>,
-    player: Query<&Transform, (With<Player>, Without<Camera2d>)>,
+    mut camera: Query<
+        (&mut Transform, &mut OrthographicProjection),
+        (With<Camera2d>, Without<Player>),
+    >,
+    // dead players are left behind, the camera stays on the last one once everyone is dead
+    players: Query<&Transform, (With<Player>, With<Actor>, Without<Camera2d>)>,
     window_query: Query<&Window, With<PrimaryWindow>>,
-    level_query: Query<&LevelIid, (Without<OrthographicProjection>, Without<Player>)>,
-    ldtk_projects: Query<&Handle<LdtkProject>>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    level_walls: Res<LevelWalls>,
 ) {
-    let Ok((mut camera, projection)) = camera.get_single_mut() else {
+    let Ok((mut camera, mut projection)) = camera.get_single_mut() else {
         debug!("Camera2d not found");
         return;
     };
 
-    let Ok(player) = player.get_single() else {
+    if players.is_empty() {
         debug!("Player not found");
         return;
-    };
+    }
 
     let Ok(window) = window_query.get_single() else {
         debug!("Window not found");
         return;
     };
 
-    let Ok(level_iid) = level_query.get_single() else {
-        debug!("Level not found");
-        return;
-    };
-
-    let ldtk_project = ldtk_project_assets
-        .get(ldtk_projects.single())
-        .expect("Project should be loaded if level has spawned");
-
-    let level = ldtk_project
-        .get_raw_level_by_iid(&level_iid.to_string())
-        .expect("Spawned level should exist in LDtk project");
+    // authored and generated levels both fill in the level size
+    let level_width = (level_walls.level_width * GRID_SIZE) as f32;
+    let level_height = (level_walls.level_height * GRID_SIZE) as f32;
 
-    let level_width = level.px_wid as f32;
-    let level_height = level.px_hei as f32;
+    let (min, max) = players.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), player| {
+            let position = player.translation.truncate();
+            (min.min(position), max.max(position))
+        },
+    );
+    let players_center = (min + max) / 2.;
 
-    let player_position = player.translation.truncate();
+    // zoom out as far as needed to keep every player in view, but never past the level
+    let span = max - min + Vec2::splat(FRAME_MARGIN * 2.);
+    let scale_to_frame = (span.x / window.width()).max(span.y / window.height());
+    let scale_to_level = (level_width / window.width()).min(level_height / window.height());
+    let scale = scale_to_frame.min(scale_to_level).max(CAMERA_SCALE);
+    if projection.scale != scale {
+        projection.scale = scale;
+    }
 
     let width_offset = window.width() / 2. * projection.scale;
     let height_offset = window.height() / 2. * projection.scale;
 
-    let direction_x = f32::clamp(player_position.x, width_offset, level_width - width_offset);
-    let direction_y = f32::clamp(
-        player_position.y,
-        height_offset,
-        level_height - height_offset,
-    );
+    let direction_x = clamp_to_level(players_center.x, width_offset, level_width);
+    let direction_y = clamp_to_level(players_center.y, height_offset, level_height);
 
     let to = Vec3::new(
         direction_x - width_offset,
@@ -75,3 +79,12 @@ fn update_camera(
 
     camera.translation = to;
 }
+
+// keeps the view inside the level, levels smaller than the view are centered
+fn clamp_to_level(position: f32, offset: f32, level_size: f32) -> f32 {
+    if level_size < offset * 2. {
+        level_size / 2.
+    } else {
+        f32::clamp(position, offset, level_size - offset)
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use bevy_asset_loader::prelude::*;
+use bevy_ecs_ldtk::GridCoords;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{stats::DamageKind, status::AppliedStatus},
+    grid::los::{has_line_of_sight, tile_distance},
+    ldtk::LevelWalls,
+};
+
+/// Number of slots in the action bar an ability can be bound to.
+pub const ABILITY_SLOTS: usize = 4;
+
+/// An ability read from a `.ability.ron` file in `assets/abilities`. Every file in the folder
+/// is bound to the action bar slot it names, new abilities need no code.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct AbilityDefinition {
+    pub name: String,
+    /// Index of the action bar slot the ability is bound to.
+    pub slot: usize,
+    /// Path of the action bar icon, relative to the assets folder.
+    pub icon: String,
+    /// Icon shown in the action bar while the ability is cooling down.
+    pub icon_on_cooldown: String,
+    /// Maximum distance in tiles to the target.
+    pub range: i32,
+    pub damage: i32,
+    /// The stat of the caster that adds to the damage, spells use spell power.
+    #[serde(default)]
+    pub damage_kind: DamageKind,
+    /// Distance in pixels the projectile travels every frame.
+    pub projectile_speed: f32,
+    /// Turns until the ability can be cast again.
+    pub cooldown: u32,
+    /// Status effect put on whatever the ability hits.
+    #[serde(default)]
+    pub status: Option<AppliedStatus>,
+    /// Player level the ability is learned at, abilities without one are known from the start.
+    #[serde(default)]
+    pub unlock_level: u32,
+    pub targeting: Targeting,
+    pub projectile: ProjectileVisuals,
+}
+
+impl AbilityDefinition {
+    /// Checks that `target` is within range of `caster` and not hidden behind a wall.
+    pub fn check_reach(
+        &self,
+        caster: &GridCoords,
+        target: &GridCoords,
+        level_walls: &LevelWalls,
+    ) -> Result<(), CastError> {
+        if tile_distance(caster, target) > self.range {
+            return Err(CastError::OutOfRange);
+        }
+
+        if !has_line_of_sight(caster, target, level_walls) {
+            return Err(CastError::NoLineOfSight);
+        }
+
+        Ok(())
+    }
+}
+
+/// Reasons a cast is blocked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastError {
+    OutOfRange,
+    NoLineOfSight,
+    InvalidTarget,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Targeting {
+    /// Has to be cast on an enemy and follows it until it hits.
+    Enemy,
+    /// Can be cast on any floor tile and hits whatever stands there on impact.
+    Tile,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct ProjectileVisuals {
+    pub radius: f32,
+    /// Color of the projectile as srgb.
+    pub color: [f32; 3],
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct AbilityAssets {
+    #[asset(path = "abilities", collection(typed))]
+    pub abilities: Vec<Handle<AbilityDefinition>>,
+}
+
+#[derive(Default)]
+pub struct AbilityDefinitionLoader;
+
+#[derive(Debug)]
+pub enum AbilityDefinitionLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for AbilityDefinitionLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbilityDefinitionLoaderError::Io(err) => write!(f, "could not read ability: {err}"),
+            AbilityDefinitionLoaderError::Ron(err) => write!(f, "could not parse ability: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for AbilityDefinitionLoaderError {}
+
+impl From<std::io::Error> for AbilityDefinitionLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        AbilityDefinitionLoaderError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for AbilityDefinitionLoaderError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        AbilityDefinitionLoaderError::Ron(err)
+    }
+}
+
+impl AssetLoader for AbilityDefinitionLoader {
+    type Asset = AbilityDefinition;
+    type Settings = ();
+    type Error = AbilityDefinitionLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ability.ron"]
+    }
+}
+
+/// Abilities bound to the action bar slots of a player.
+#[derive(Component, Default, Debug)]
+pub struct AbilitySlots(pub [Option<Handle<AbilityDefinition>>; ABILITY_SLOTS]);
+
+impl AbilitySlots {
+    /// Binds the abilities a player at `level` knows to the slots they name.
+    pub fn from_definitions(
+        handles: &[Handle<AbilityDefinition>],
+        definitions: &Assets<AbilityDefinition>,
+        level: u32,
+    ) -> Self {
+        let mut slots = AbilitySlots::default();
+        for handle in handles {
+            let Some(definition) = definitions.get(handle) else {
+                continue;
+            };
+            if definition.unlock_level > level {
+                continue;
+            }
+
+            match slots.0.get_mut(definition.slot) {
+                Some(slot) if slot.is_none() => *slot = Some(handle.clone()),
+                Some(_) => warn!("Ability slot {} is bound twice", definition.slot),
+                None => warn!(
+                    "{} is bound to unknown slot {}",
+                    definition.name, definition.slot
+                ),
+            }
+        }
+        slots
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&Handle<AbilityDefinition>> {
+        self.0.get(slot).and_then(Option::as_ref)
+    }
+
+    pub fn contains(&self, handle: &Handle<AbilityDefinition>) -> bool {
+        self.0.iter().flatten().any(|bound| bound == handle)
+    }
+
+    /// Binds a newly learned ability to the slot it names, or to the first free one if that is
+    /// taken. Returns the slot, `None` if all of them are taken.
+    pub fn unlock(
+        &mut self,
+        handle: &Handle<AbilityDefinition>,
+        definition: &AbilityDefinition,
+    ) -> Option<usize> {
+        let slot = match self.0.get(definition.slot) {
+            Some(None) => definition.slot,
+            _ => self.0.iter().position(Option::is_none)?,
+        };
+        self.0[slot] = Some(handle.clone());
+        Some(slot)
+    }
+}
+
+/// Turns left until the ability in each action bar slot can be cast again.
+#[derive(Component, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AbilityCooldowns(pub [u32; ABILITY_SLOTS]);
+
+impl AbilityCooldowns {
+    pub fn remaining(&self, slot: usize) -> u32 {
+        self.0.get(slot).copied().unwrap_or_default()
+    }
+
+    pub fn is_ready(&self, slot: usize) -> bool {
+        self.remaining(slot) == 0
+    }
+
+    pub fn start(&mut self, slot: usize, definition: &AbilityDefinition) {
+        if let Some(remaining) = self.0.get_mut(slot) {
+            // the turn spent casting counts down right away
+            *remaining = definition.cooldown + 1;
+        }
+    }
+
+    pub fn tick(&mut self) {
+        for remaining in self.0.iter_mut() {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+}
@@ -1,157 +1,463 @@
-use bevy::{
-    color::palettes::css,
-    prelude::*,
-    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
-};
-use bevy_ecs_ldtk::GridCoords;
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+use bevy_asset_loader::prelude::*;
+use bevy_ecs_ldtk::{utils::grid_coords_to_translation, GridCoords};
 use leafwing_input_manager::action_state::ActionState;
 
+pub mod ability;
+pub mod stats;
+pub mod status;
+
+use ability::{
+    AbilityAssets, AbilityCooldowns, AbilityDefinition, AbilityDefinitionLoader, AbilitySlots,
+    CastError, Targeting,
+};
+use stats::{hit_damage, update_stats, BaseStats, DamageKind, Stats};
+use status::{
+    skip_stunned_players, slow_actors, tick_status_effects, AppliedStatus, StatusEffects,
+};
+
 use crate::{
     enemy::Enemy,
-    get_single, get_single_mut,
-    input::PlayerInputAction,
+    events::{PlayerActed, PlayerTurnAction, TurnEnded},
+    fov::FieldOfView,
+    input::{InputDevices, PlayerInputAction},
+    ldtk::LevelWalls,
     player::{Player, PlayerAction},
-    ui::game_cursor::{AttackCursor, CursorPos},
-    AppState, Health, ABILITY_Z_INDEX,
+    progression::STARTING_LEVEL,
+    replay::Replay,
+    turn::{emit_turn_ended, ActionCost, TurnQueue, TurnSet},
+    ui::game_cursor::{AttackCursor, CursorPos, GameCursor},
+    AppState, Health, ABILITY_Z_INDEX, GRID_SIZE, LEVEL_LOADED,
 };
 
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<AbilityDefinition>()
+            .init_asset_loader::<AbilityDefinitionLoader>()
+            .add_loading_state(
+                LoadingState::new(AppState::Loading)
+                    .continue_to_state(AppState::InGame)
+                    .load_collection::<AbilityAssets>(),
+            )
+            .init_resource::<CursorPos>()
+            .add_event::<CastAbility>()
+            .add_systems(LEVEL_LOADED, equip_abilities)
+            .add_systems(
+                Update,
+                (read_ability_input, update_stats).run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    // effects tick right after the turns they end, a stun is already known when
+                    // the next turn starts
+                    (
+                        tick_ability_cooldowns,
+                        (tick_status_effects, slow_actors).chain(),
+                    )
+                        .after(emit_turn_ended),
+                    skip_stunned_players.before(TurnSet::Player),
+                    cast_ability.in_set(TurnSet::Player),
+                    // a projectile flies for several ticks while the enemies keep taking their
+                    // turns, a hit in a tick lands before the enemies act in that same tick
+                    move_projectiles
+                        .after(TurnSet::Player)
+                        .before(TurnSet::Enemy),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnEnter(AppState::GameOver), despawn_abilities)
+            .observe(on_target_hit)
+            .register_type::<Stats>()
+            .register_type::<BaseStats>()
+            .register_type::<StatusEffects>();
+    }
+}
+
+/// Meshes and materials of abilities, left out when running headless.
+pub struct CombatVisualsPlugin;
+
+impl Plugin for CombatVisualsPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            (fireball, move_fireball).run_if(in_state(AppState::InGame)),
-        )
-        .observe(on_target_hit);
+            add_projectile_mesh.run_if(in_state(AppState::InGame)),
+        );
     }
 }
 
 #[derive(Component)]
 struct Ability {
-    target: Entity,
+    target: AbilityTarget,
     origin: Entity,
+    definition: Handle<AbilityDefinition>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbilityTarget {
+    Entity(Entity),
+    Tile(GridCoords),
+}
+
+/// Asks to cast the ability in `slot` of `caster` at `target`. Casts that are not possible right
+/// now are dropped.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CastAbility {
+    pub caster: Entity,
+    pub slot: usize,
+    pub target: GridCoords,
 }
 
 #[derive(Component)]
-struct Fireball {
+struct Projectile {
     speed: f32,
     damage: i32,
+    kind: DamageKind,
+    status: Option<AppliedStatus>,
 }
 
+/// Triggered when an attack lands. `ability` is the projectile that hit, if there was one, it is
+/// despawned on impact. `damage` is the base damage of the attack, the [`Stats`] of `origin` and
+/// `target` decide how much of it is dealt. `status` is put on the target on top of the damage.
 #[derive(Event)]
-struct HitEvent {
-    target: Entity,
-    origin: Entity,
-    ability: Entity,
-    damage: i32,
+pub struct HitEvent {
+    pub target: Entity,
+    pub origin: Entity,
+    pub ability: Option<Entity>,
+    pub damage: i32,
+    pub kind: DamageKind,
+    pub status: Option<AppliedStatus>,
 }
 
-const FIREBALL_DAMAGE: i32 = 50;
+/// The `origin` of the last [`HitEvent`] that landed on an entity, whoever killed it gets the
+/// credit.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LastHitBy(pub Entity);
+
+/// What a cast of `definition` from `caster` at `target_pos` would hit, or why the cast is
+/// blocked. `enemy` is the enemy standing on `target_pos`, if there is one.
+pub fn cast_target(
+    definition: &AbilityDefinition,
+    caster: &GridCoords,
+    target_pos: &GridCoords,
+    enemy: Option<Entity>,
+    level_walls: &LevelWalls,
+) -> Result<AbilityTarget, CastError> {
+    let target = match (definition.targeting, enemy) {
+        (Targeting::Enemy, Some(enemy)) => AbilityTarget::Entity(enemy),
+        (Targeting::Enemy, None) => return Err(CastError::InvalidTarget),
+        (Targeting::Tile, _) if level_walls.in_wall(target_pos) => {
+            return Err(CastError::InvalidTarget)
+        }
+        (Targeting::Tile, _) => AbilityTarget::Tile(*target_pos),
+    };
+
+    definition.check_reach(caster, target_pos, level_walls)?;
+    Ok(target)
+}
 
-//TODO: isntead of checking for enemies, we rather should use a 'target' component.
-fn fireball(
+fn equip_abilities(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut player_action: Query<&mut PlayerAction, With<Player>>,
-    input_q: Query<&ActionState<PlayerInputAction>, With<Player>>,
-    player_q: Query<(Entity, &Transform), With<Player>>,
-    enemies_q: Query<(Entity, &GridCoords), With<Enemy>>,
-    attack_cursor_q: Query<&AttackCursor>,
+    players: Query<Entity, (With<Player>, Without<AbilitySlots>)>,
+    ability_assets: Res<AbilityAssets>,
+    definitions: Res<Assets<AbilityDefinition>>,
+) {
+    for entity in players.iter() {
+        commands.entity(entity).insert((
+            // the abilities learned on the way are added once the player's level is known
+            AbilitySlots::from_definitions(&ability_assets.abilities, &definitions, STARTING_LEVEL),
+            AbilityCooldowns::default(),
+        ));
+    }
+}
+
+fn tick_ability_cooldowns(
+    mut turn_ended_er: EventReader<TurnEnded>,
+    mut cooldowns_q: Query<&mut AbilityCooldowns>,
+) {
+    for TurnEnded(entity) in turn_ended_er.read() {
+        if let Ok(mut cooldowns) = cooldowns_q.get_mut(*entity) {
+            cooldowns.tick();
+        }
+    }
+}
+
+/// Casts the pressed ability at the cursor, unless a [`Replay`] is running in place of input.
+/// Players on a gamepad have no cursor and aim at the closest enemy they can see.
+#[allow(clippy::type_complexity)]
+pub fn read_ability_input(
+    players: Query<
+        (
+            Entity,
+            &GridCoords,
+            &ActionState<PlayerInputAction>,
+            &AbilitySlots,
+            Option<&InputDevices>,
+            Option<&FieldOfView>,
+        ),
+        With<Player>,
+    >,
+    enemies: Query<&GridCoords, With<Enemy>>,
+    game_cursor: Query<Has<AttackCursor>, With<GameCursor>>,
+    definitions: Res<Assets<AbilityDefinition>>,
     cursor_pos: Res<CursorPos>,
+    replay: Option<Res<Replay>>,
+    mut cast_ability_ew: EventWriter<CastAbility>,
 ) {
-    // only allow to cast fireball when the player has a target
-    // TODO: account for the range of spells
-    if attack_cursor_q.get_single().is_err() {
+    if replay.is_some() {
         return;
     }
 
-    let action_state = input_q.single();
-    let mut player_action = get_single_mut!(player_action);
+    for (player_entity, player_pos, action_state, slots, devices, field_of_view) in players.iter() {
+        let Some(slot) = PlayerInputAction::ABILITIES
+            .iter()
+            .filter(|action| action_state.just_pressed(action))
+            .filter_map(|action| action.ability_slot())
+            .find(|slot| slots.get(*slot).is_some())
+        else {
+            continue;
+        };
 
-    let enemy_entity = enemies_q.iter().find_map(|(entity, coords)| {
-        if coords == &cursor_pos.world_position() {
-            Some(entity)
+        let target = if devices.map_or(true, |devices| devices.uses_mouse()) {
+            // only cast at an enemy when the cursor shows it can be attacked, headless runs have
+            // no cursor to ask
+            let targets_enemy = slots
+                .get(slot)
+                .and_then(|handle| definitions.get(handle))
+                .is_some_and(|definition| definition.targeting == Targeting::Enemy);
+            if targets_enemy && game_cursor.get_single().is_ok_and(|attack| !attack) {
+                continue;
+            }
+            cursor_pos.world_position()
         } else {
-            None
+            let Some(target) = enemies
+                .iter()
+                .filter(|enemy_pos| {
+                    field_of_view
+                        .is_some_and(|field_of_view| field_of_view.visible.contains(enemy_pos))
+                })
+                .min_by_key(|enemy_pos| {
+                    (enemy_pos.x - player_pos.x)
+                        .abs()
+                        .max((enemy_pos.y - player_pos.y).abs())
+                })
+            else {
+                continue;
+            };
+            *target
+        };
+
+        cast_ability_ew.send(CastAbility {
+            caster: player_entity,
+            slot,
+            target,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn cast_ability(
+    mut commands: Commands,
+    mut cast_ability_er: EventReader<CastAbility>,
+    mut players: Query<
+        (
+            &Transform,
+            &GridCoords,
+            &AbilitySlots,
+            &mut AbilityCooldowns,
+            &mut PlayerAction,
+        ),
+        With<Player>,
+    >,
+    enemies_q: Query<(Entity, &GridCoords), With<Enemy>>,
+    definitions: Res<Assets<AbilityDefinition>>,
+    level_walls: Res<LevelWalls>,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut player_acted_ew: EventWriter<PlayerActed>,
+) {
+    for cast in cast_ability_er.read() {
+        let player_entity = cast.caster;
+        let Ok((transform, player_pos, slots, mut cooldowns, mut player_action)) =
+            players.get_mut(player_entity)
+        else {
+            continue;
+        };
+
+        if matches!(*player_action, PlayerAction::Combat | PlayerAction::Dead)
+            || !turn_queue.is_turn_of(player_entity)
+        {
+            continue;
         }
-    });
 
-    let enemy_entity = match enemy_entity {
-        Some(entity) => entity,
-        None => return,
-    };
+        let Some(handle) = slots.get(cast.slot) else {
+            continue;
+        };
+
+        if !cooldowns.is_ready(cast.slot) {
+            continue;
+        }
+
+        let Some(definition) = definitions.get(handle) else {
+            continue;
+        };
+
+        let target_pos = cast.target;
+        let enemy = enemies_q
+            .iter()
+            .find(|(_, coords)| **coords == target_pos)
+            .map(|(entity, _)| entity);
+
+        let Ok(target) = cast_target(definition, player_pos, &target_pos, enemy, &level_walls)
+        else {
+            continue;
+        };
 
-    if action_state.just_pressed(&PlayerInputAction::Ability1)
-        && *player_action != PlayerAction::Combat
-    {
         *player_action = PlayerAction::Combat;
-        let (player_entity, fireball_transform) = get_single!(player_q);
-        let mut fireball_transform = *fireball_transform;
-        fireball_transform.translation.z = ABILITY_Z_INDEX;
-        let fireball_entity = commands
-            .spawn((
-                Ability {
-                    origin: player_entity,
-                    target: enemy_entity,
-                },
-                Fireball {
-                    speed: 1.0,
-                    damage: FIREBALL_DAMAGE,
-                },
-                Name::new("Fireball"),
-            ))
-            .id();
+        cooldowns.start(cast.slot, definition);
+        turn_queue.end_turn(player_entity, ActionCost::Cast);
+        player_acted_ew.send(PlayerActed {
+            player: player_entity,
+            action: PlayerTurnAction::Cast {
+                slot: cast.slot,
+                target: target_pos.into(),
+            },
+        });
 
-        commands
-            .entity(fireball_entity)
-            .insert((MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(meshes.add(Circle::new(2.0))),
-                material: materials.add(Color::from(css::RED)),
-                transform: fireball_transform,
-                ..default()
-            },));
+        let mut projectile_transform = *transform;
+        projectile_transform.translation.z = ABILITY_Z_INDEX;
+        commands.spawn((
+            Ability {
+                origin: player_entity,
+                target,
+                definition: handle.clone(),
+            },
+            Projectile {
+                speed: definition.projectile_speed,
+                damage: definition.damage,
+                kind: definition.damage_kind,
+                status: definition.status,
+            },
+            Name::new(definition.name.clone()),
+            SpatialBundle::from_transform(projectile_transform),
+        ));
     }
 }
 
-fn move_fireball(
-    mut fireball_q: Query<(Entity, &mut Transform, &Fireball, &Ability), With<Ability>>,
-    enemies_q: Query<&Transform, (With<Enemy>, Without<Ability>)>,
+fn add_projectile_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    definitions: Res<Assets<AbilityDefinition>>,
+    projectile_q: Query<(Entity, &Ability), Added<Projectile>>,
+) {
+    for (entity, ability) in projectile_q.iter() {
+        let Some(definition) = definitions.get(&ability.definition) else {
+            continue;
+        };
+
+        let [red, green, blue] = definition.projectile.color;
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(Circle::new(definition.projectile.radius))),
+            materials.add(Color::srgb(red, green, blue)),
+        ));
+    }
+}
+
+pub fn move_projectiles(
+    mut projectile_q: Query<(Entity, &mut Transform, &Projectile, &Ability)>,
+    targets_q: Query<(Entity, &Transform, &GridCoords), (With<Health>, Without<Ability>)>,
     mut commands: Commands,
     mut player_action: Query<&mut PlayerAction, With<Player>>,
 ) {
-    let mut player_action = get_single_mut!(player_action);
-    for (ability_entity, mut transform, fire_ball, ability) in fireball_q.iter_mut() {
-        let target = enemies_q.get(ability.target).unwrap();
+    for (ability_entity, mut transform, projectile, ability) in projectile_q.iter_mut() {
+        let destination = match ability.target {
+            AbilityTarget::Entity(target) => match targets_q.get(target) {
+                Ok((_, target_transform, _)) => target_transform.translation.truncate(),
+                // the target died before the projectile arrived
+                Err(_) => {
+                    commands.entity(ability_entity).despawn_recursive();
+                    reset_caster(ability.origin, &mut player_action);
+                    continue;
+                }
+            },
+            AbilityTarget::Tile(coords) => {
+                grid_coords_to_translation(coords, IVec2::splat(GRID_SIZE))
+            }
+        };
 
-        let direction = target.translation - transform.translation;
+        let direction = destination - transform.translation.truncate();
         let distance = direction.length();
-        let direction = direction.normalize();
-        let movement = direction * fire_ball.speed;
 
         if distance < 1.0 {
-            commands.trigger(HitEvent {
-                target: ability.target,
-                origin: ability.origin,
-                ability: ability_entity,
-                damage: fire_ball.damage,
-            });
-            *player_action = PlayerAction::Idle;
+            let hit = match ability.target {
+                AbilityTarget::Entity(target) => Some(target),
+                AbilityTarget::Tile(coords) => targets_q
+                    .iter()
+                    .find(|(entity, _, target_coords)| {
+                        **target_coords == coords && *entity != ability.origin
+                    })
+                    .map(|(entity, _, _)| entity),
+            };
+
+            match hit {
+                Some(target) => commands.trigger(HitEvent {
+                    target,
+                    origin: ability.origin,
+                    ability: Some(ability_entity),
+                    damage: projectile.damage,
+                    kind: projectile.kind,
+                    status: projectile.status,
+                }),
+                None => commands.entity(ability_entity).despawn_recursive(),
+            }
+            reset_caster(ability.origin, &mut player_action);
         } else {
-            transform.translation += movement;
+            let movement = direction.normalize() * projectile.speed.min(distance);
+            transform.translation += movement.extend(0.0);
         }
     }
 }
 
+fn reset_caster(origin: Entity, player_action: &mut Query<&mut PlayerAction, With<Player>>) {
+    if let Ok(mut player_action) = player_action.get_mut(origin) {
+        if *player_action != PlayerAction::Dead {
+            *player_action = PlayerAction::Idle;
+        }
+    }
+}
+
+// projectiles are not part of the level and would outlive it
+fn despawn_abilities(mut commands: Commands, abilities_q: Query<Entity, With<Ability>>) {
+    for entity in abilities_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 fn on_target_hit(
     trigger: Trigger<HitEvent>,
-    mut health_q: Query<&mut Health>,
+    mut health_q: Query<(&mut Health, Option<&mut StatusEffects>)>,
+    stats_q: Query<&Stats>,
     mut commands: Commands,
 ) {
     let hit_event = trigger.event();
-    commands.entity(hit_event.ability).despawn();
-    if let Ok(mut target_health) = health_q.get_mut(hit_event.target) {
-        target_health.current_health -= hit_event.damage;
+    if let Some(ability) = hit_event.ability {
+        commands.entity(ability).despawn();
+    }
+    if let Ok((mut target_health, status_effects)) = health_q.get_mut(hit_event.target) {
+        target_health.current_health -= hit_damage(
+            hit_event.damage,
+            hit_event.kind,
+            stats_q.get(hit_event.origin).ok(),
+            stats_q.get(hit_event.target).ok(),
+        );
+        commands
+            .entity(hit_event.target)
+            .insert(LastHitBy(hit_event.origin));
+
+        if let (Some(status), Some(mut status_effects)) = (hit_event.status, status_effects) {
+            status_effects.apply(status, Some(hit_event.origin));
+        }
     }
 }
@@ -0,0 +1,170 @@
+use std::ops::Add;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{item::Equipment, Health};
+
+// even the best armor doesn't make a hit harmless
+const MIN_DAMAGE: i32 = 1;
+
+/// Combat stats of an actor, what its [`BaseStats`] and the [`Equipment`] it wears add up to.
+/// Kept up to date by [`update_stats`].
+#[derive(
+    Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize,
+)]
+pub struct Stats {
+    /// Added to the damage of physical hits.
+    pub attack: i32,
+    /// Taken off the damage of every hit.
+    pub defense: i32,
+    /// Added to the damage of magical hits.
+    pub spell_power: i32,
+    pub max_health: i32,
+}
+
+impl Add for Stats {
+    type Output = Stats;
+
+    fn add(self, other: Stats) -> Stats {
+        Stats {
+            attack: self.attack + other.attack,
+            defense: self.defense + other.defense,
+            spell_power: self.spell_power + other.spell_power,
+            max_health: self.max_health + other.max_health,
+        }
+    }
+}
+
+/// Stats of an actor without any equipment.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub struct BaseStats(pub Stats);
+
+/// Which stat of the attacker makes a hit stronger.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Deserialize)]
+pub enum DamageKind {
+    /// Scales with [`Stats::attack`], like the bite of a slime or a thrown rock.
+    Physical,
+    /// Scales with [`Stats::spell_power`].
+    #[default]
+    Magical,
+}
+
+/// Damage a hit with `base_damage` deals. Hits from or on something without stats are not
+/// changed by them.
+pub fn hit_damage(
+    base_damage: i32,
+    kind: DamageKind,
+    attacker: Option<&Stats>,
+    defender: Option<&Stats>,
+) -> i32 {
+    let offense = attacker.map_or(0, |stats| match kind {
+        DamageKind::Physical => stats.attack,
+        DamageKind::Magical => stats.spell_power,
+    });
+    let defense = defender.map_or(0, |stats| stats.defense);
+
+    (base_damage + offense - defense).max(MIN_DAMAGE)
+}
+
+/// Adds the equipment up with the base stats. The damage an actor has taken stays the same
+/// when its maximum health changes, so swapping gear back and forth doesn't heal.
+#[allow(clippy::type_complexity)]
+pub fn update_stats(
+    mut actors: Query<
+        (
+            &BaseStats,
+            Option<&Equipment>,
+            &mut Stats,
+            Option<&mut Health>,
+        ),
+        Or<(Changed<BaseStats>, Changed<Equipment>)>,
+    >,
+) {
+    for (base_stats, equipment, mut stats, health) in actors.iter_mut() {
+        let new_stats = base_stats.0 + equipment.map(Equipment::bonus).unwrap_or_default();
+        stats.set_if_neq(new_stats);
+
+        if let Some(mut health) = health {
+            if health.max_health != new_stats.max_health {
+                let damage_taken = health.max_health - health.current_health;
+                health.max_health = new_stats.max_health;
+                // losing maximum health doesn't kill, but the dead stay dead
+                if health.current_health > 0 {
+                    health.current_health = (new_stats.max_health - damage_taken).max(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(attack: i32, defense: i32, spell_power: i32) -> Stats {
+        Stats {
+            attack,
+            defense,
+            spell_power,
+            max_health: 100,
+        }
+    }
+
+    #[test]
+    fn offense_depends_on_the_damage_kind() {
+        let attacker = stats(3, 0, 7);
+
+        assert_eq!(
+            hit_damage(10, DamageKind::Physical, Some(&attacker), None),
+            13
+        );
+        assert_eq!(
+            hit_damage(10, DamageKind::Magical, Some(&attacker), None),
+            17
+        );
+    }
+
+    #[test]
+    fn defense_is_taken_off_the_damage() {
+        let defender = stats(0, 4, 0);
+
+        assert_eq!(
+            hit_damage(10, DamageKind::Physical, None, Some(&defender)),
+            6
+        );
+    }
+
+    #[test]
+    fn high_defense_still_takes_the_minimum_damage() {
+        let attacker = stats(2, 0, 0);
+        let defender = stats(0, 50, 0);
+
+        assert_eq!(
+            hit_damage(10, DamageKind::Physical, Some(&attacker), Some(&defender)),
+            MIN_DAMAGE
+        );
+        assert_eq!(
+            hit_damage(0, DamageKind::Magical, None, Some(&defender)),
+            MIN_DAMAGE
+        );
+    }
+
+    #[test]
+    fn missing_stats_leave_the_damage_as_it_is() {
+        assert_eq!(hit_damage(10, DamageKind::Magical, None, None), 10);
+    }
+
+    #[test]
+    fn stats_add_up_field_by_field() {
+        assert_eq!(
+            stats(1, 2, 3) + stats(4, 5, 6),
+            Stats {
+                attack: 5,
+                defense: 7,
+                spell_power: 9,
+                max_health: 200,
+            }
+        );
+    }
+}
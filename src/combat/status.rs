@@ -0,0 +1,362 @@
+use bevy::{color::palettes::css, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::TurnEnded,
+    player::Player,
+    turn::{ActionCost, Actor, TurnQueue},
+    Health,
+};
+
+use super::LastHitBy;
+
+// slowed actors act this many times less often
+const SLOW_FACTOR: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// Deals damage at the end of every turn.
+    Burn,
+    /// Deals damage at the end of every turn, stacks up when applied again.
+    Poison,
+    /// Takes turns less often.
+    Slow,
+    /// Skips its turns.
+    Stun,
+    /// Can't move, but still attack and cast.
+    Root,
+}
+
+impl StatusKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusKind::Burn => "Burn",
+            StatusKind::Poison => "Poison",
+            StatusKind::Slow => "Slow",
+            StatusKind::Stun => "Stun",
+            StatusKind::Root => "Root",
+        }
+    }
+
+    /// Damage every stack deals at the end of a turn of the affected actor.
+    pub fn damage_per_turn(self) -> i32 {
+        match self {
+            StatusKind::Burn => 5,
+            StatusKind::Poison => 3,
+            StatusKind::Slow | StatusKind::Stun | StatusKind::Root => 0,
+        }
+    }
+
+    /// Stacks the effect builds up to, applying it again beyond that only refreshes it.
+    pub fn max_stacks(self) -> u32 {
+        match self {
+            StatusKind::Poison => 5,
+            StatusKind::Burn | StatusKind::Slow | StatusKind::Stun | StatusKind::Root => 1,
+        }
+    }
+
+    /// Color of the icon on health bars and the player widget.
+    pub fn color(self) -> Srgba {
+        match self {
+            StatusKind::Burn => css::ORANGE_RED,
+            StatusKind::Poison => css::MEDIUM_PURPLE,
+            StatusKind::Slow => css::LIGHT_BLUE,
+            StatusKind::Stun => css::YELLOW,
+            StatusKind::Root => css::SADDLE_BROWN,
+        }
+    }
+}
+
+/// A status effect a hit applies, e.g. the `status` of an ability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, Deserialize)]
+pub struct AppliedStatus {
+    pub kind: StatusKind,
+    /// Turns of the target the effect lasts.
+    pub turns: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns_left: u32,
+    pub stacks: u32,
+    /// Whoever applied the effect, credited with the kill if its damage finishes the actor.
+    /// Not saved, a loaded run starts with new entities.
+    #[serde(skip)]
+    pub source: Option<Entity>,
+}
+
+/// Status effects on an actor. They tick at the end of each of its turns, see
+/// [`tick_status_effects`].
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    /// Adds an effect from `source`. An effect that is already there gains a stack, up to its
+    /// [`StatusKind::max_stacks`], and lasts at least as long as the new one.
+    pub fn apply(&mut self, status: AppliedStatus, source: Option<Entity>) {
+        match self.0.iter_mut().find(|effect| effect.kind == status.kind) {
+            Some(effect) => {
+                effect.stacks = (effect.stacks + 1).min(status.kind.max_stacks());
+                effect.turns_left = effect.turns_left.max(status.turns);
+                effect.source = source.or(effect.source);
+            }
+            None => self.0.push(StatusEffect {
+                kind: status.kind,
+                turns_left: status.turns,
+                stacks: 1,
+                source,
+            }),
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.0.iter().any(|effect| effect.kind == kind)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.0.iter()
+    }
+
+    /// Counts down a turn of every effect and removes the ones that ran out. Returns the damage
+    /// they dealt and who the last of it came from.
+    pub fn tick(&mut self) -> (i32, Option<Entity>) {
+        let mut damage = 0;
+        let mut source = None;
+        for effect in self.0.iter_mut() {
+            let effect_damage = effect.kind.damage_per_turn() * effect.stacks as i32;
+            if effect_damage > 0 {
+                damage += effect_damage;
+                source = effect.source.or(source);
+            }
+            effect.turns_left = effect.turns_left.saturating_sub(1);
+        }
+        self.0.retain(|effect| effect.turns_left > 0);
+
+        (damage, source)
+    }
+}
+
+/// Ticks the status effects of every actor that ended its turn. Their damage ignores stats.
+pub fn tick_status_effects(
+    mut commands: Commands,
+    mut turn_ended_er: EventReader<TurnEnded>,
+    mut actors: Query<(&mut StatusEffects, &mut Health)>,
+) {
+    for TurnEnded(entity) in turn_ended_er.read() {
+        let Ok((mut status_effects, mut health)) = actors.get_mut(*entity) else {
+            continue;
+        };
+        if status_effects.0.is_empty() {
+            continue;
+        }
+
+        let (damage, source) = status_effects.tick();
+        if damage > 0 && health.current_health > 0 {
+            health.current_health -= damage;
+            if let Some(source) = source {
+                commands.entity(*entity).insert(LastHitBy(source));
+            }
+        }
+    }
+}
+
+/// Keeps the speed an actor has in the [`TurnQueue`] in line with it being slowed.
+pub fn slow_actors(
+    actors: Query<(Entity, &Actor, &StatusEffects), Changed<StatusEffects>>,
+    mut turn_queue: ResMut<TurnQueue>,
+) {
+    for (entity, actor, status_effects) in actors.iter() {
+        let speed = if status_effects.has(StatusKind::Slow) {
+            actor.speed / SLOW_FACTOR
+        } else {
+            actor.speed
+        };
+        turn_queue.set_speed(entity, speed);
+    }
+}
+
+/// Ends the turn of a stunned player. Only one turn per run, a player alone in the queue would
+/// otherwise be skipped forever before the stun can tick down. Enemies skip their own turns
+/// when they are up.
+pub fn skip_stunned_players(
+    mut turn_queue: ResMut<TurnQueue>,
+    players: Query<&StatusEffects, With<Player>>,
+) {
+    let Some(current) = turn_queue.current() else {
+        return;
+    };
+
+    if players
+        .get(current)
+        .is_ok_and(|status_effects| status_effects.has(StatusKind::Stun))
+    {
+        info!("Stunned, the turn is skipped");
+        turn_queue.end_turn(current, ActionCost::Wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::turn::NORMAL_SPEED;
+
+    fn applied(kind: StatusKind, turns: u32) -> AppliedStatus {
+        AppliedStatus { kind, turns }
+    }
+
+    fn effect(status_effects: &StatusEffects, kind: StatusKind) -> &StatusEffect {
+        status_effects
+            .iter()
+            .find(|effect| effect.kind == kind)
+            .expect("the effect is applied")
+    }
+
+    #[test]
+    fn applying_again_stacks_up_to_the_limit() {
+        let mut status_effects = StatusEffects::default();
+
+        for _ in 0..StatusKind::Poison.max_stacks() + 2 {
+            status_effects.apply(applied(StatusKind::Poison, 3), None);
+        }
+
+        assert_eq!(status_effects.0.len(), 1);
+        assert_eq!(
+            effect(&status_effects, StatusKind::Poison).stacks,
+            StatusKind::Poison.max_stacks()
+        );
+    }
+
+    #[test]
+    fn applying_again_refreshes_but_never_shortens() {
+        let mut status_effects = StatusEffects::default();
+
+        status_effects.apply(applied(StatusKind::Burn, 2), None);
+        status_effects.apply(applied(StatusKind::Burn, 4), None);
+        assert_eq!(effect(&status_effects, StatusKind::Burn).turns_left, 4);
+
+        status_effects.apply(applied(StatusKind::Burn, 1), None);
+        let burn = effect(&status_effects, StatusKind::Burn);
+        assert_eq!(burn.turns_left, 4);
+        assert_eq!(burn.stacks, 1);
+    }
+
+    #[test]
+    fn applying_without_a_source_keeps_the_old_one() {
+        let source = Entity::from_raw(1);
+        let mut status_effects = StatusEffects::default();
+
+        status_effects.apply(applied(StatusKind::Poison, 3), Some(source));
+        status_effects.apply(applied(StatusKind::Poison, 3), None);
+
+        assert_eq!(
+            effect(&status_effects, StatusKind::Poison).source,
+            Some(source)
+        );
+    }
+
+    #[test]
+    fn tick_deals_damage_for_every_stack() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(applied(StatusKind::Poison, 3), None);
+        status_effects.apply(applied(StatusKind::Poison, 3), None);
+        status_effects.apply(applied(StatusKind::Slow, 3), None);
+
+        let (damage, _) = status_effects.tick();
+
+        assert_eq!(damage, 2 * StatusKind::Poison.damage_per_turn());
+    }
+
+    #[test]
+    fn effects_run_out_after_their_turns() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(applied(StatusKind::Stun, 1), None);
+        status_effects.apply(applied(StatusKind::Root, 2), None);
+
+        status_effects.tick();
+        assert!(!status_effects.has(StatusKind::Stun));
+        assert!(status_effects.has(StatusKind::Root));
+
+        status_effects.tick();
+        assert!(status_effects.0.is_empty());
+    }
+
+    #[test]
+    fn tick_credits_whoever_applied_the_damage() {
+        let burner = Entity::from_raw(1);
+        let stunner = Entity::from_raw(2);
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(applied(StatusKind::Burn, 3), Some(burner));
+        // effects without damage don't take the credit
+        status_effects.apply(applied(StatusKind::Stun, 3), Some(stunner));
+
+        assert_eq!(
+            status_effects.tick(),
+            (StatusKind::Burn.damage_per_turn(), Some(burner))
+        );
+    }
+
+    /// A player and an enemy that is up right after them.
+    fn world_with_player(status_effects: StatusEffects) -> (World, Entity, Entity) {
+        let mut world = World::new();
+        let player = world.spawn((Player, status_effects)).id();
+        let enemy = world.spawn(StatusEffects::default()).id();
+
+        let mut turn_queue = TurnQueue::default();
+        turn_queue.insert(
+            player,
+            &Actor {
+                speed: NORMAL_SPEED,
+                initiative: 1,
+            },
+        );
+        turn_queue.insert(
+            enemy,
+            &Actor {
+                speed: NORMAL_SPEED,
+                initiative: 0,
+            },
+        );
+        world.insert_resource(turn_queue);
+
+        (world, player, enemy)
+    }
+
+    #[test]
+    fn stunned_player_skips_the_turn() {
+        let mut stunned = StatusEffects::default();
+        stunned.apply(applied(StatusKind::Stun, 1), None);
+        let (mut world, _, enemy) = world_with_player(stunned);
+
+        world.run_system_once(skip_stunned_players);
+
+        assert!(world.resource::<TurnQueue>().is_turn_of(enemy));
+    }
+
+    #[test]
+    fn player_without_stun_keeps_the_turn() {
+        let mut rooted = StatusEffects::default();
+        rooted.apply(applied(StatusKind::Root, 1), None);
+        let (mut world, player, _) = world_with_player(rooted);
+
+        world.run_system_once(skip_stunned_players);
+
+        assert!(world.resource::<TurnQueue>().is_turn_of(player));
+    }
+
+    #[test]
+    fn stunned_player_is_skipped_once_per_run() {
+        let mut stunned = StatusEffects::default();
+        stunned.apply(applied(StatusKind::Stun, 1), None);
+        let (mut world, player, enemy) = world_with_player(stunned.clone());
+        world.entity_mut(enemy).insert((Player, stunned));
+
+        world.run_system_once(skip_stunned_players);
+
+        let turn_queue = world.resource::<TurnQueue>();
+        assert!(turn_queue.is_turn_of(enemy));
+        assert!(!turn_queue.is_turn_of(player));
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+
+use bevy_ecs_ldtk::GridCoords;
+use rand::Rng;
+
+use crate::{
+    item::Item,
+    ldtk::{LayoutEntity, LevelLayout},
+};
+
+const LEVEL_WIDTH: i32 = 48;
+const LEVEL_HEIGHT: i32 = 32;
+const MAX_ROOMS: usize = 10;
+// attempts to place a room before giving up on it
+const ROOM_ATTEMPTS: usize = 100;
+const MIN_ROOM_SIZE: i32 = 4;
+const MAX_ROOM_SIZE: i32 = 9;
+const MAX_SLIMES_PER_ROOM: usize = 3;
+// chance of a room to have an item lying in it
+const ITEM_CHANCE: f64 = 0.4;
+// how often each item is found compared to the others
+const ITEM_WEIGHTS: [(Item, u32); 6] = [
+    (Item::Potion, 12),
+    (Item::Scroll, 6),
+    (Item::Sword, 1),
+    (Item::Staff, 1),
+    (Item::Armor, 1),
+    (Item::Amulet, 1),
+];
+
+/// A rectangle of floor, `min` and `max` are included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Room {
+    min: GridCoords,
+    max: GridCoords,
+}
+
+impl Room {
+    fn center(&self) -> GridCoords {
+        GridCoords::new((self.min.x + self.max.x) / 2, (self.min.y + self.max.y) / 2)
+    }
+
+    /// Overlaps with `other` or touches it, rooms keep at least one wall between them.
+    fn intersects(&self, other: &Room) -> bool {
+        self.min.x <= other.max.x + 2
+            && self.max.x + 2 >= other.min.x
+            && self.min.y <= other.max.y + 2
+            && self.max.y + 2 >= other.min.y
+    }
+
+    fn tiles(&self) -> impl Iterator<Item = GridCoords> + '_ {
+        (self.min.x..=self.max.x)
+            .flat_map(move |x| (self.min.y..=self.max.y).map(move |y| GridCoords::new(x, y)))
+    }
+
+    fn corners(&self) -> Vec<GridCoords> {
+        vec![
+            self.min,
+            GridCoords::new(self.max.x, self.min.y),
+            self.max,
+            GridCoords::new(self.min.x, self.max.y),
+        ]
+    }
+}
+
+/// Rooms and corridors levels for the floors below the authored ones, `depth` counts the
+/// generated floors. The same `rng` state always produces the same level.
+pub fn generate_level(rng: &mut impl Rng, depth: usize) -> LevelLayout {
+    let rooms = place_rooms(rng);
+
+    let mut floor: HashSet<GridCoords> = rooms.iter().flat_map(Room::tiles).collect();
+
+    // connect every room to the previous one with an L shaped corridor
+    for pair in rooms.windows(2) {
+        let (from, to) = (pair[0].center(), pair[1].center());
+        let corner = if rng.gen_bool(0.5) {
+            GridCoords::new(to.x, from.y)
+        } else {
+            GridCoords::new(from.x, to.y)
+        };
+
+        floor.extend(straight_line(from, corner));
+        floor.extend(straight_line(corner, to));
+    }
+
+    let walls = floor
+        .iter()
+        .flat_map(|coords| {
+            (-1..=1).flat_map(move |x| {
+                (-1..=1).map(move |y| GridCoords::new(coords.x + x, coords.y + y))
+            })
+        })
+        .filter(|coords| !floor.contains(coords))
+        .collect();
+
+    let mut layout = LevelLayout {
+        width: LEVEL_WIDTH,
+        height: LEVEL_HEIGHT,
+        walls,
+        floor,
+        entities: Vec::new(),
+    };
+
+    let Some((player, stairs)) = player_and_stairs(&rooms) else {
+        return layout;
+    };
+
+    let mut spawn = |identifier: &str, coords: GridCoords, patrol_route: Vec<GridCoords>| {
+        let index = layout.entities.len();
+        layout.entities.push(LayoutEntity {
+            identifier: identifier.to_string(),
+            iid: format!("generated-{depth}-{index}"),
+            coords,
+            patrol_route,
+        });
+    };
+
+    spawn("Player", player, Vec::new());
+    spawn("Stairs", stairs, Vec::new());
+
+    // deeper floors get crowded
+    let max_slimes = (1 + depth / 4).min(MAX_SLIMES_PER_ROOM);
+    let mut occupied = vec![player, stairs];
+    for room in rooms.iter().skip(1) {
+        for slime in 0..rng.gen_range(1..=max_slimes) {
+            let coords = GridCoords::new(
+                rng.gen_range(room.min.x..=room.max.x),
+                rng.gen_range(room.min.y..=room.max.y),
+            );
+            if occupied.contains(&coords) {
+                continue;
+            }
+            occupied.push(coords);
+
+            // the first slime of a room walks along its corners
+            let patrol_route = if slime == 0 {
+                room.corners()
+            } else {
+                Vec::new()
+            };
+            spawn("Slime", coords, patrol_route);
+        }
+    }
+
+    // placed after the slimes, so a seed still puts them where it did before there were items
+    for room in rooms.iter() {
+        if !rng.gen_bool(ITEM_CHANCE) {
+            continue;
+        }
+
+        let coords = GridCoords::new(
+            rng.gen_range(room.min.x..=room.max.x),
+            rng.gen_range(room.min.y..=room.max.y),
+        );
+        if occupied.contains(&coords) {
+            continue;
+        }
+        occupied.push(coords);
+
+        spawn(random_item(rng).identifier(), coords, Vec::new());
+    }
+
+    layout
+}
+
+/// Rooms that don't touch each other, at least one is always placed.
+fn place_rooms(rng: &mut impl Rng) -> Vec<Room> {
+    let mut rooms: Vec<Room> = Vec::new();
+    for _ in 0..ROOM_ATTEMPTS {
+        if rooms.len() >= MAX_ROOMS {
+            break;
+        }
+
+        let width = rng.gen_range(MIN_ROOM_SIZE..=MAX_ROOM_SIZE);
+        let height = rng.gen_range(MIN_ROOM_SIZE..=MAX_ROOM_SIZE);
+        // keep the outermost ring free for walls
+        let min = GridCoords::new(
+            rng.gen_range(1..LEVEL_WIDTH - width - 1),
+            rng.gen_range(1..LEVEL_HEIGHT - height - 1),
+        );
+        let room = Room {
+            min,
+            max: GridCoords::new(min.x + width - 1, min.y + height - 1),
+        };
+
+        if !rooms.iter().any(|other| other.intersects(&room)) {
+            rooms.push(room);
+        }
+    }
+
+    rooms
+}
+
+/// Where the player starts and where the stairs are, in the first and the last room. A level
+/// with a single room puts the stairs into its far corner instead of onto the player.
+fn player_and_stairs(rooms: &[Room]) -> Option<(GridCoords, GridCoords)> {
+    let (first, last) = (rooms.first()?, rooms.last()?);
+    let stairs = if rooms.len() > 1 {
+        last.center()
+    } else {
+        last.max
+    };
+
+    Some((first.center(), stairs))
+}
+
+fn random_item(rng: &mut impl Rng) -> Item {
+    let total: u32 = ITEM_WEIGHTS.iter().map(|(_, weight)| weight).sum();
+    let mut roll = rng.gen_range(0..total);
+    for (item, weight) in ITEM_WEIGHTS {
+        if roll < weight {
+            return item;
+        }
+        roll -= weight;
+    }
+    unreachable!("the roll is below the total weight")
+}
+
+/// Tiles from `from` to `to` along a horizontal or vertical line.
+fn straight_line(from: GridCoords, to: GridCoords) -> Vec<GridCoords> {
+    let (min_x, max_x) = (from.x.min(to.x), from.x.max(to.x));
+    let (min_y, max_y) = (from.y.min(to.y), from.y.max(to.y));
+
+    (min_x..=max_x)
+        .flat_map(|x| (min_y..=max_y).map(move |y| GridCoords::new(x, y)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const SEEDS: std::ops::Range<u64> = 0..50;
+
+    fn entity_coords(layout: &LevelLayout, identifier: &str) -> GridCoords {
+        layout
+            .entities
+            .iter()
+            .find(|entity| entity.identifier == identifier)
+            .map(|entity| entity.coords)
+            .unwrap_or_else(|| panic!("the level has a {identifier}"))
+    }
+
+    /// Floor tiles that can be walked to from `start` with straight steps.
+    fn reachable_floor(layout: &LevelLayout, start: GridCoords) -> HashSet<GridCoords> {
+        let mut reached = HashSet::from([start]);
+        let mut open = vec![start];
+        while let Some(coords) = open.pop() {
+            for step in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let next = GridCoords::new(coords.x + step.0, coords.y + step.1);
+                if layout.floor.contains(&next) && reached.insert(next) {
+                    open.push(next);
+                }
+            }
+        }
+        reached
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_level() {
+        let first = generate_level(&mut ChaCha8Rng::seed_from_u64(7), 0);
+        let second = generate_level(&mut ChaCha8Rng::seed_from_u64(7), 0);
+
+        assert_eq!(first.floor, second.floor);
+        let coords =
+            |layout: &LevelLayout| layout.entities.iter().map(|e| e.coords).collect::<Vec<_>>();
+        assert_eq!(coords(&first), coords(&second));
+    }
+
+    #[test]
+    fn rooms_do_not_overlap() {
+        for seed in SEEDS {
+            let rooms = place_rooms(&mut ChaCha8Rng::seed_from_u64(seed));
+
+            assert!(!rooms.is_empty());
+            for (index, room) in rooms.iter().enumerate() {
+                for other in &rooms[index + 1..] {
+                    assert!(!room.intersects(other), "seed {seed}: {room:?} {other:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_floor_tile_is_reachable_from_the_spawn() {
+        for seed in SEEDS {
+            let layout = generate_level(&mut ChaCha8Rng::seed_from_u64(seed), 0);
+            let spawn = entity_coords(&layout, "Player");
+
+            let reachable = reachable_floor(&layout, spawn);
+
+            assert!(reachable.contains(&entity_coords(&layout, "Stairs")));
+            assert_eq!(reachable, layout.floor, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn spawn_and_stairs_are_on_different_tiles() {
+        for seed in SEEDS {
+            let layout = generate_level(&mut ChaCha8Rng::seed_from_u64(seed), 0);
+
+            assert_ne!(
+                entity_coords(&layout, "Player"),
+                entity_coords(&layout, "Stairs"),
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn single_room_puts_the_stairs_away_from_the_spawn() {
+        let room = Room {
+            min: GridCoords::new(2, 2),
+            max: GridCoords::new(2 + MIN_ROOM_SIZE - 1, 2 + MIN_ROOM_SIZE - 1),
+        };
+
+        let (player, stairs) = player_and_stairs(&[room]).expect("there is a room");
+
+        assert_ne!(player, stairs);
+        assert!(room.tiles().any(|coords| coords == stairs));
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+pub mod generator;
+
+use generator::generate_level;
+
+use crate::{
+    enemy::{slime::Slime, PatrolRoute},
+    item::Item,
+    ldtk::{Floor, LevelFloor, LevelLayout, LevelWalls, Stair, Wall},
+    player::Player,
+    rng::GameRng,
+    AppState, GRID_SIZE,
+};
+
+/// Generates the floors below the last authored level, so descending never runs out.
+pub struct DungeonPlugin;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AuthoredLevels>()
+            .add_systems(OnEnter(AppState::Loading), spawn_generated_level);
+    }
+}
+
+/// Sprites for generated levels, left out when running headless.
+pub struct DungeonVisualsPlugin;
+
+impl Plugin for DungeonVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_dungeon_tileset)
+            .observe(add_layout_sprites);
+    }
+}
+
+// z-index of entities spawned from a layout, above walls and floor
+const LAYOUT_ENTITY_Z_INDEX: f32 = 2.0;
+
+// tiles of `dungeon/set_1.png` the authored levels use for plain floor and walls
+const TILESET_COLUMNS: u32 = 36;
+const TILESET_ROWS: u32 = 18;
+const FLOOR_TILE: usize = 457;
+const WALL_TILE: usize = 523;
+const STAIRS_TILE: usize = 602;
+
+/// Number of levels in the LDtk project, levels past it are generated. `None` until the project
+/// is loaded.
+#[derive(Resource, Default, Debug, PartialEq, Eq)]
+pub struct AuthoredLevels(pub Option<usize>);
+
+/// Root of all entities of a generated level.
+#[derive(Component)]
+pub struct GeneratedLevel;
+
+/// Marks entities spawned from a [`LevelLayout`] instead of by the LDtk plugin.
+#[derive(Component)]
+pub struct LayoutSpawned;
+
+#[derive(Resource)]
+struct DungeonTileset {
+    layout: Handle<TextureAtlasLayout>,
+    texture: Handle<Image>,
+}
+
+/// Spawns the walls, floor and entities of `layout` below a new root entity and returns it.
+pub fn spawn_level_layout(commands: &mut Commands, layout: &LevelLayout, name: String) -> Entity {
+    commands
+        .spawn((Name::new(name), SpatialBundle::default()))
+        .with_children(|level| {
+            for &coords in layout.walls.iter() {
+                level.spawn((Wall, coords, tile_bundle(coords, 0.0), LayoutSpawned));
+            }
+
+            for &coords in layout.floor.iter() {
+                level.spawn((Floor, coords, tile_bundle(coords, 0.0), LayoutSpawned));
+            }
+
+            for entity in layout.entities.iter() {
+                let mut entity_commands = level.spawn((
+                    entity.coords,
+                    tile_bundle(entity.coords, LAYOUT_ENTITY_Z_INDEX),
+                    Name::new(entity.identifier.clone()),
+                    // the same iid the LDtk plugin would give it, saves identify entities by it
+                    EntityIid::new(entity.iid.clone()),
+                ));
+
+                match entity.identifier.as_str() {
+                    "Player" => {
+                        entity_commands.insert(Player);
+                    }
+                    "Stairs" => {
+                        entity_commands.insert(Stair);
+                    }
+                    "Slime" => {
+                        entity_commands
+                            .insert((Slime, PatrolRoute::new(entity.patrol_route.clone())));
+                    }
+                    identifier => match Item::from_identifier(identifier) {
+                        Some(item) => {
+                            entity_commands.insert(item);
+                        }
+                        None => warn!("Unknown entity {identifier} in level layout"),
+                    },
+                }
+
+                // after the marker components, so the sprites know what they are drawing
+                entity_commands.insert(LayoutSpawned);
+            }
+        })
+        .id()
+}
+
+fn tile_bundle(coords: GridCoords, z: f32) -> SpatialBundle {
+    SpatialBundle::from_transform(Transform::from_translation(
+        bevy_ecs_ldtk::utils::grid_coords_to_translation(coords, IVec2::splat(GRID_SIZE)).extend(z),
+    ))
+}
+
+fn spawn_generated_level(
+    mut commands: Commands,
+    level_selection: Res<LevelSelection>,
+    authored_levels: Res<AuthoredLevels>,
+    game_rng: Res<GameRng>,
+    previous_levels: Query<Entity, With<GeneratedLevel>>,
+    mut level_walls: ResMut<LevelWalls>,
+    mut level_floor: ResMut<LevelFloor>,
+) {
+    for entity in previous_levels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let index = match level_selection.into_inner() {
+        LevelSelection::Indices(indices) => indices.level,
+        _ => panic!("level selection should always be Indices in this game"),
+    };
+
+    let Some(authored_levels) = authored_levels.0 else {
+        return;
+    };
+    if index < authored_levels {
+        return;
+    }
+
+    let depth = index - authored_levels;
+    let layout = generate_level(&mut game_rng.level_rng(depth), depth);
+    *level_walls = layout.level_walls();
+    *level_floor = layout.level_floor();
+
+    let level = spawn_level_layout(&mut commands, &layout, format!("Generated_{index}"));
+    commands.entity(level).insert(GeneratedLevel);
+}
+
+fn load_dungeon_tileset(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    commands.insert_resource(DungeonTileset {
+        layout: layouts.add(TextureAtlasLayout::from_grid(
+            UVec2::splat(GRID_SIZE as u32),
+            TILESET_COLUMNS,
+            TILESET_ROWS,
+            None,
+            None,
+        )),
+        texture: asset_server.load("dungeon/set_1.png"),
+    });
+}
+
+fn add_layout_sprites(
+    trigger: Trigger<OnAdd, LayoutSpawned>,
+    mut commands: Commands,
+    tiles: Query<(Has<Wall>, Has<Floor>, Has<Stair>)>,
+    tileset: Res<DungeonTileset>,
+) {
+    let entity = trigger.entity();
+    let Ok((wall, floor, stair)) = tiles.get(entity) else {
+        return;
+    };
+
+    let tile = match (wall, floor, stair) {
+        (true, _, _) => Some(WALL_TILE),
+        (_, true, _) => Some(FLOOR_TILE),
+        (_, _, true) => Some(STAIRS_TILE),
+        // characters get their sprite sheets patched in when the level starts
+        _ => None,
+    };
+
+    let (texture, atlas) = match tile {
+        Some(index) => (
+            tileset.texture.clone(),
+            TextureAtlas {
+                layout: tileset.layout.clone(),
+                index,
+            },
+        ),
+        None => (Handle::default(), TextureAtlas::default()),
+    };
+
+    commands
+        .entity(entity)
+        .insert((Sprite::default(), texture, atlas));
+}
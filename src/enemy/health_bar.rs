@@ -1,17 +1,43 @@
 use bevy::{
+    color::palettes::css,
     prelude::*,
     render::render_resource::{AsBindGroup, ShaderRef},
-    sprite::{Material2d, Material2dPlugin},
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
 };
 
+use crate::{combat::status::StatusEffects, events::CombatEvent, AppState, Health};
+
+use super::Enemy;
+
 pub struct HealthBarPlugin;
 
 impl Plugin for HealthBarPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(Material2dPlugin::<HealthBarMaterial>::default());
+        app.add_plugins(Material2dPlugin::<HealthBarMaterial>::default())
+            .add_systems(
+                Update,
+                (
+                    spawn_health_bars,
+                    show_healthbar,
+                    update_health_bar,
+                    update_status_icons,
+                )
+                    .run_if(in_state(AppState::InGame)),
+            );
     }
 }
 
+#[derive(Component)]
+pub struct HealthBar;
+
+// status icons are lined up above the health bar, from its left end on
+const STATUS_ICON_SIZE: f32 = 2.0;
+const STATUS_ICON_SPACING: f32 = 3.0;
+
+/// One of the [`StatusEffects`] of an enemy, a child of its [`HealthBar`].
+#[derive(Component)]
+struct StatusIcon;
+
 #[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
 pub struct HealthBarMaterial {
     #[uniform(0)]
@@ -27,3 +53,96 @@ impl Material2d for HealthBarMaterial {
         "shaders/health_bar.wgsl".into()
     }
 }
+
+fn spawn_health_bars(
+    mut commands: Commands,
+    enemies: Query<Entity, Added<Enemy>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut health_bar_materials: ResMut<Assets<HealthBarMaterial>>,
+) {
+    for entity in enemies.iter() {
+        let healt_bar = commands
+            .spawn((
+                HealthBar,
+                MaterialMesh2dBundle {
+                    mesh: Mesh2dHandle(meshes.add(Rectangle::new(10.0, 1.0))),
+                    material: health_bar_materials.add(HealthBarMaterial {
+                        foreground_color: css::GREEN.into(),
+                        background_color: css::RED.into(),
+                        percent: 1.,
+                    }),
+                    transform: Transform::from_xyz(0., 8., 0.),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ))
+            .id();
+
+        commands.entity(entity).add_child(healt_bar);
+    }
+}
+
+fn show_healthbar(
+    mut combat_et_reader: EventReader<CombatEvent>,
+    mut health_bars: Query<&mut Visibility, With<HealthBar>>,
+) {
+    if let Some(event) = combat_et_reader.read().next() {
+        for mut health_bar in health_bars.iter_mut() {
+            if event.0 {
+                *health_bar = Visibility::Visible;
+            }
+            if !event.0 {
+                *health_bar = Visibility::Hidden;
+            }
+        }
+    }
+}
+
+fn update_health_bar(
+    mut health_bar_materials: ResMut<Assets<HealthBarMaterial>>,
+    enemies_q: Query<(&Health, &Children), With<Enemy>>,
+    healthbar_material_q: Query<&Handle<HealthBarMaterial>>,
+) {
+    for (health, children) in enemies_q.iter() {
+        for &child in children.iter() {
+            let per = health.current_health as f32 / health.max_health as f32;
+            let Ok(handle) = healthbar_material_q.get(child) else {
+                continue;
+            };
+            let material = health_bar_materials.get_mut(handle).unwrap();
+            material.percent = per;
+        }
+    }
+}
+
+fn update_status_icons(
+    mut commands: Commands,
+    enemies_q: Query<(&StatusEffects, &Children), (With<Enemy>, Changed<StatusEffects>)>,
+    health_bars: Query<Entity, With<HealthBar>>,
+) {
+    for (status_effects, children) in enemies_q.iter() {
+        for health_bar in health_bars.iter_many(children) {
+            commands.entity(health_bar).despawn_descendants();
+
+            for (i, effect) in status_effects.iter().enumerate() {
+                let x = -5.0 + STATUS_ICON_SIZE / 2.0 + i as f32 * STATUS_ICON_SPACING;
+                let icon = commands
+                    .spawn((
+                        StatusIcon,
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: effect.kind.color().into(),
+                                custom_size: Some(Vec2::splat(STATUS_ICON_SIZE)),
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(x, 2.5, 0.1),
+                            ..default()
+                        },
+                        Name::new(effect.kind.name()),
+                    ))
+                    .id();
+                commands.entity(health_bar).add_child(icon);
+            }
+        }
+    }
+}
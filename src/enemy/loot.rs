@@ -0,0 +1,103 @@
+use std::fmt;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use bevy_asset_loader::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::item::Item;
+
+/// What an enemy type drops when it dies, read from a `.loot.ron` file in `assets/loot`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct LootTable {
+    /// Number of entries drawn, the same entry can be drawn more than once.
+    pub rolls: u32,
+    pub entries: Vec<LootEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct LootEntry {
+    /// `None` drops nothing, it makes the other entries less likely.
+    pub item: Option<Item>,
+    pub weight: u32,
+}
+
+impl LootTable {
+    /// Draws the items a dying enemy leaves behind.
+    pub fn roll(&self, rng: &mut impl Rng) -> Vec<Item> {
+        (0..self.rolls)
+            .filter_map(|_| {
+                self.entries
+                    .choose_weighted(rng, |entry| entry.weight)
+                    .ok()
+                    .and_then(|entry| entry.item)
+            })
+            .collect()
+    }
+}
+
+/// The loot table of an enemy.
+#[derive(Component, Debug, Clone)]
+pub struct Loot(pub Handle<LootTable>);
+
+#[derive(AssetCollection, Resource)]
+pub struct LootAssets {
+    #[asset(path = "loot/slime.loot.ron")]
+    pub slime: Handle<LootTable>,
+}
+
+#[derive(Default)]
+pub struct LootTableLoader;
+
+#[derive(Debug)]
+pub enum LootTableLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for LootTableLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LootTableLoaderError::Io(err) => write!(f, "could not read loot table: {err}"),
+            LootTableLoaderError::Ron(err) => write!(f, "could not parse loot table: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LootTableLoaderError {}
+
+impl From<std::io::Error> for LootTableLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        LootTableLoaderError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for LootTableLoaderError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        LootTableLoaderError::Ron(err)
+    }
+}
+
+impl AssetLoader for LootTableLoader {
+    type Asset = LootTable;
+    type Settings = ();
+    type Error = LootTableLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["loot.ron"]
+    }
+}
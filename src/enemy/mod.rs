@@ -1,41 +1,86 @@
 use bevy::prelude::*;
-use bevy_ecs_ldtk::GridCoords;
-use health_bar::HealthBarMaterial;
+use bevy_asset_loader::prelude::*;
+use bevy_ecs_ldtk::{EntityIid, GridCoords};
+use rand::seq::SliceRandom;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 pub mod health_bar;
+pub mod loot;
 pub mod slime;
 
+use loot::{Loot, LootAssets, LootTable, LootTableLoader};
+
 use crate::{
-    events::CombatEvent, get_single, grid::GridPosition, ldtk::LevelWalls, player::Player,
-    AppState, Health,
+    combat::{move_projectiles, LastHitBy},
+    events::CombatEvent,
+    grid::{Collider, GridPosition},
+    item::spawn_item,
+    ldtk::LevelWalls,
+    player::Player,
+    rng::GameRng,
+    run::RunStats,
+    turn::{Actor, TurnSet},
+    AppState, Health, ACTION_DELAY,
 };
 
 pub struct EnemyPlugin;
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                player_enemy_range_detection,
-                show_healthbar,
-                handle_attacking_mark,
-                update_health_bar,
+        app.init_asset::<LootTable>()
+            .init_asset_loader::<LootTableLoader>()
+            .add_loading_state(
+                LoadingState::new(AppState::Loading)
+                    .continue_to_state(AppState::InGame)
+                    .load_collection::<LootAssets>(),
+            )
+            .add_event::<EnemyDied>()
+            // the dead, behaviors and marks have to reflect the player's turn before the enemies
+            // take theirs
+            .add_systems(
+                FixedUpdate,
+                (
+                    kill_enemies.after(move_projectiles),
+                    player_enemy_range_detection,
+                    handle_attacking_mark,
+                )
+                    .chain()
+                    .after(TurnSet::Player)
+                    .before(TurnSet::Enemy)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                PostUpdate,
+                despawn_dying_enemies.run_if(in_state(AppState::InGame)),
             )
-                .run_if(in_state(AppState::InGame)),
-        )
-        .add_systems(
-            PostUpdate,
-            despawn_dead_enemies.run_if(in_state(AppState::InGame)),
-        )
-        .register_type::<EnemyBehaviorState>()
-        .register_type::<Health>()
-        .register_type::<Enemy>();
+            .register_type::<EnemyBehaviorState>()
+            .register_type::<AttackDamage>()
+            .register_type::<ExperienceReward>()
+            .register_type::<Cornered>()
+            .register_type::<PatrolRoute>()
+            .register_type::<Health>()
+            .register_type::<Enemy>();
     }
 }
 
+// the five frames of the death animation and a moment on the last one
+pub const DEATH_DURATION: f32 = ACTION_DELAY / 4.0 * 6.0;
+
+/// Sent when an enemy runs out of health, after its loot has been dropped. `killer` is the
+/// origin of the hit that finished it off.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct EnemyDied {
+    pub enemy: Entity,
+    pub coords: GridCoords,
+    pub killer: Option<Entity>,
+    /// Experience the killer is rewarded with.
+    pub experience: u32,
+}
+
+/// An enemy that ran out of health. It no longer is an [`Enemy`], takes turns or gets hit, it
+/// only plays its death animation until the timer runs out and it is despawned.
 #[derive(Component)]
-pub struct HealthBar;
+pub struct Dying(pub Timer);
 
 #[derive(Component, Reflect)]
 pub struct AttackRange(i32);
@@ -46,10 +91,38 @@ impl Default for AttackRange {
     }
 }
 
+#[derive(Component, Reflect)]
+pub struct AttackDamage(pub i32);
+
+impl Default for AttackDamage {
+    fn default() -> Self {
+        AttackDamage(10)
+    }
+}
+
+/// Experience an enemy is worth to the player that kills it.
+#[derive(Component, Default, Reflect)]
+pub struct ExperienceReward(pub u32);
+
 #[derive(Component, Default, Reflect)]
 pub struct EnemyAttacking;
 
-#[derive(Default, Reflect, PartialEq)]
+/// Marks an enemy that had nowhere left to flee to, it fights and regenerates until it has
+/// recovered.
+#[derive(Component, Default, Reflect)]
+pub struct Cornered;
+
+// enemies start fleeing below this fraction of their health and keep fleeing until recovered
+pub const FLEE_HEALTH_FRACTION: f32 = 0.3;
+pub const RECOVERED_HEALTH_FRACTION: f32 = 0.6;
+// health regained for every turn spent fleeing or cornered
+pub const FLEE_REGENERATION: i32 = 2;
+// tiles further away from the player than this are considered safe
+const FLEE_SEARCH_DISTANCE: u32 = 20;
+// enemies closer than this on both axes notice the player
+const DETECTION_RANGE: i32 = 5;
+
+#[derive(Default, Clone, Copy, Debug, Reflect, PartialEq, Serialize, Deserialize)]
 pub enum EnemyBehaviorState {
     #[default]
     Idle,
@@ -58,6 +131,63 @@ pub enum EnemyBehaviorState {
     Patrolling,
 }
 
+/// Waypoints an enemy walks along while it hasn't spotted the player, authored as the `Patrol`
+/// field of enemies in LDtk.
+#[derive(Component, Default, Debug, Clone, Reflect)]
+pub struct PatrolRoute {
+    pub waypoints: Vec<GridCoords>,
+    pub next_waypoint: usize,
+}
+
+impl PatrolRoute {
+    pub fn new(waypoints: Vec<GridCoords>) -> Self {
+        Self {
+            waypoints,
+            next_waypoint: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waypoints.is_empty()
+    }
+
+    /// Continues the route at the waypoint closest to `position`.
+    pub fn resume_from(&mut self, position: &GridCoords) {
+        if let Some((index, _)) = self
+            .waypoints
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, waypoint)| GridPosition::new(**waypoint).heuristic(position))
+        {
+            self.next_waypoint = index;
+        }
+    }
+
+    /// Direction of the next step along the route, moving on to the following waypoint once
+    /// the current one has been reached.
+    pub fn next_step(
+        &mut self,
+        position: &GridCoords,
+        level_walls: &LevelWalls,
+        occupied_coords: &[GridCoords],
+    ) -> GridCoords {
+        if self.waypoints.is_empty() {
+            return GridCoords::new(0, 0);
+        }
+
+        if self.waypoints[self.next_waypoint] == *position {
+            self.next_waypoint = (self.next_waypoint + 1) % self.waypoints.len();
+        }
+
+        let goal = self.waypoints[self.next_waypoint];
+        GridPosition::new(*position)
+            .pathfind(goal, level_walls, Some(occupied_coords))
+            .and_then(|path| path.get(1).copied())
+            .map(|next| next - *position)
+            .unwrap_or(GridCoords::new(0, 0))
+    }
+}
+
 #[derive(Component, Default, Reflect)]
 pub struct Enemy {
     pub behavior_state: EnemyBehaviorState,
@@ -70,26 +200,30 @@ impl Enemy {
         enemy_pos: &GridCoords,
         level_walls: &LevelWalls,
         occupied_coords: &[GridCoords],
+        rng: &mut impl Rng,
     ) -> GridCoords {
         match self.behavior_state {
+            // wander to a random free neighbour, or stay put if there is none
             EnemyBehaviorState::Idle => {
-                let next_pos;
-                loop {
-                    let mut rng = rand::thread_rng();
-                    let x = rng.gen_range(-1..=1);
-                    let y = rng.gen_range(-1..=1);
-                    let new_coords = GridCoords::new(enemy_pos.x + x, enemy_pos.y + y);
-                    if !level_walls.wall_locations.contains(&new_coords)
-                        && !occupied_coords.contains(&new_coords)
-                        && new_coords != *enemy_pos
-                    {
-                        next_pos = GridCoords::new(x, y);
-                        break;
-                    }
-                }
-                next_pos
+                let free_neighbours: Vec<GridCoords> = (-1..=1)
+                    .flat_map(|x| (-1..=1).map(move |y| GridCoords::new(x, y)))
+                    .filter(|offset| *offset != GridCoords::new(0, 0))
+                    .filter(|offset| {
+                        let new_coords = *enemy_pos + *offset;
+                        !level_walls.in_wall(&new_coords)
+                            && !level_walls.cuts_corner(enemy_pos, *offset)
+                            && !occupied_coords.contains(&new_coords)
+                    })
+                    .collect();
+
+                free_neighbours
+                    .choose(rng)
+                    .copied()
+                    .unwrap_or(GridCoords::new(0, 0))
             }
-            EnemyBehaviorState::Fleeing => todo!(),
+            EnemyBehaviorState::Fleeing => self
+                .flee(player_pos, enemy_pos, level_walls, occupied_coords)
+                .unwrap_or(GridCoords::new(0, 0)),
             EnemyBehaviorState::Pursuing => {
                 let start_pos = GridPosition::new(enemy_pos.to_owned());
                 let path =
@@ -107,34 +241,117 @@ impl Enemy {
                 };
                 next_pos
             }
-            EnemyBehaviorState::Patrolling => todo!(),
+            // patrolling enemies follow their PatrolRoute instead
+            EnemyBehaviorState::Patrolling => GridCoords::new(0, 0),
         }
     }
+
+    /// Picks the neighbouring tile with the longest path to the player. Returns `None` if the
+    /// enemy is cornered, i.e. no free neighbour is further away from the player than the enemy
+    /// itself.
+    pub fn flee(
+        &self,
+        player_pos: &GridCoords,
+        enemy_pos: &GridCoords,
+        level_walls: &LevelWalls,
+        occupied_coords: &[GridCoords],
+    ) -> Option<GridCoords> {
+        let distances = GridPosition::new(*player_pos).distances(level_walls, FLEE_SEARCH_DISTANCE);
+        let distance_to_player =
+            |coords: &GridCoords| *distances.get(coords).unwrap_or(&(FLEE_SEARCH_DISTANCE + 1));
+
+        let current_distance = distance_to_player(enemy_pos);
+        if current_distance > FLEE_SEARCH_DISTANCE {
+            // safe, stay put and recover
+            return Some(GridCoords::new(0, 0));
+        }
+
+        GridPosition::new(*enemy_pos)
+            .successors(enemy_pos, level_walls, Some(occupied_coords))
+            .iter()
+            .map(|successor| successor.coords.0)
+            .filter(|coords| !level_walls.in_wall(coords) && coords != player_pos)
+            .map(|coords| (distance_to_player(&coords), coords))
+            .filter(|(distance, _)| *distance > current_distance)
+            .max_by_key(|(distance, _)| *distance)
+            .map(|(_, coords)| coords - *enemy_pos)
+    }
+}
+
+/// The closest of `players` to the enemy at `enemy_pos`, the one it goes after.
+pub fn nearest_player<'a>(
+    enemy_pos: &GridCoords,
+    players: impl Iterator<Item = &'a (Entity, GridCoords)>,
+) -> Option<(Entity, GridCoords)> {
+    players
+        .min_by_key(|(_, player_pos)| {
+            (player_pos.x - enemy_pos.x)
+                .abs()
+                .max((player_pos.y - enemy_pos.y).abs())
+        })
+        .copied()
 }
 
+/// Whether an enemy at `enemy_pos` is close enough to the player to go after it.
+pub fn in_detection_range(player_pos: &GridCoords, enemy_pos: &GridCoords) -> bool {
+    (player_pos.x - enemy_pos.x).abs() < DETECTION_RANGE
+        && (player_pos.y - enemy_pos.y).abs() < DETECTION_RANGE
+}
+
+#[allow(clippy::type_complexity)]
 fn player_enemy_range_detection(
-    player_pos: Query<&GridCoords, With<Player>>,
-    mut enemies: Query<(&GridCoords, &mut Enemy)>,
+    mut commands: Commands,
+    players: Query<&GridCoords, (With<Player>, With<Actor>)>,
+    mut enemies: Query<(
+        Entity,
+        &GridCoords,
+        &mut Enemy,
+        &Health,
+        Has<Cornered>,
+        Option<&mut PatrolRoute>,
+    )>,
     mut combat_event: EventWriter<CombatEvent>,
 ) {
-    let player = if let Ok(player_pos) = player_pos.get_single() {
-        player_pos
-    } else {
+    // dead players left the turn queue, enemies don't go after them
+    if players.is_empty() {
         return;
-    };
+    }
 
     let mut is_in_combat = false;
 
-    for (enemy_pos, mut enemy) in enemies.iter_mut() {
-        let x_diff = (player.x - enemy_pos.x).abs();
-        let y_diff = (player.y - enemy_pos.y).abs();
+    for (entity, enemy_pos, mut enemy, health, cornered, patrol_route) in enemies.iter_mut() {
+        let in_range = players
+            .iter()
+            .any(|player_pos| in_detection_range(player_pos, enemy_pos));
 
-        if x_diff < 5 && y_diff < 5 {
-            enemy.behavior_state = EnemyBehaviorState::Pursuing;
+        let recovered = health.fraction() >= RECOVERED_HEALTH_FRACTION;
+        if recovered && cornered {
+            commands.entity(entity).remove::<Cornered>();
+        }
+
+        let flee = !cornered
+            && if enemy.behavior_state == EnemyBehaviorState::Fleeing {
+                !recovered
+            } else {
+                health.fraction() <= FLEE_HEALTH_FRACTION
+            };
+
+        if in_range {
             is_in_combat = true;
-        } else {
-            enemy.behavior_state = EnemyBehaviorState::Idle;
         }
+
+        enemy.behavior_state = match patrol_route {
+            _ if flee => EnemyBehaviorState::Fleeing,
+            _ if in_range => EnemyBehaviorState::Pursuing,
+            Some(mut patrol_route) if !patrol_route.is_empty() => {
+                // lost the player, head back to the closest waypoint
+                if enemy.behavior_state != EnemyBehaviorState::Patrolling {
+                    patrol_route.resume_from(enemy_pos);
+                }
+                EnemyBehaviorState::Patrolling
+            }
+            _ => EnemyBehaviorState::Idle,
+        };
     }
 
     combat_event.send(CombatEvent(is_in_combat));
@@ -142,15 +359,16 @@ fn player_enemy_range_detection(
 
 fn handle_attacking_mark(
     mut commands: Commands,
-    player_pos: Query<&GridCoords, With<Player>>,
+    players: Query<&GridCoords, (With<Player>, With<Actor>)>,
     enemies: Query<(Entity, &GridCoords, &AttackRange), With<Enemy>>,
 ) {
-    let player_pos = get_single!(player_pos);
     for (entity, enemy_pos, attack_range) in enemies.iter() {
-        let x_diff = (player_pos.x - enemy_pos.x).abs();
-        let y_diff = (player_pos.y - enemy_pos.y).abs();
+        let in_attack_range = players.iter().any(|player_pos| {
+            (player_pos.x - enemy_pos.x).abs() <= attack_range.0
+                && (player_pos.y - enemy_pos.y).abs() <= attack_range.0
+        });
 
-        if x_diff <= attack_range.0 && y_diff <= attack_range.0 {
+        if in_attack_range {
             commands.entity(entity).insert(EnemyAttacking);
         } else {
             commands.entity(entity).remove::<EnemyAttacking>();
@@ -158,40 +376,74 @@ fn handle_attacking_mark(
     }
 }
 
-fn show_healthbar(
-    mut combat_et_reader: EventReader<CombatEvent>,
-    mut health_bars: Query<&mut Visibility, With<HealthBar>>,
+#[allow(clippy::type_complexity)]
+fn kill_enemies(
+    mut commands: Commands,
+    enemies_q: Query<
+        (
+            Entity,
+            &Health,
+            &GridCoords,
+            Option<&Loot>,
+            Option<&LastHitBy>,
+            Option<&ExperienceReward>,
+            Option<&EntityIid>,
+            Option<&Parent>,
+        ),
+        With<Enemy>,
+    >,
+    loot_tables: Res<Assets<LootTable>>,
+    mut game_rng: ResMut<GameRng>,
+    mut run_stats: ResMut<RunStats>,
+    mut enemy_died_ew: EventWriter<EnemyDied>,
 ) {
-    if let Some(event) = combat_et_reader.read().next() {
-        for mut health_bar in health_bars.iter_mut() {
-            if event.0 {
-                *health_bar = Visibility::Visible;
-            }
-            if !event.0 {
-                *health_bar = Visibility::Hidden;
-            }
+    for (entity, health, coords, loot, last_hit_by, experience, iid, parent) in enemies_q.iter() {
+        if health.current_health > 0 {
+            continue;
         }
-    }
-}
 
-fn update_health_bar(
-    mut health_bar_materials: ResMut<Assets<HealthBarMaterial>>,
-    enemies_q: Query<(&Health, &Children), With<Enemy>>,
-    healthbar_material_q: Query<&Handle<HealthBarMaterial>>,
-) {
-    for (health, children) in enemies_q.iter() {
-        for &child in children.iter() {
-            let per = health.current_health as f32 / health.max_health as f32;
-            let handle = healthbar_material_q.get(child).unwrap();
-            let material = health_bar_materials.get_mut(handle).unwrap();
-            material.percent = per;
+        run_stats.enemies_killed += 1;
+
+        let drops = loot
+            .and_then(|loot| loot_tables.get(&loot.0))
+            .map(|loot_table| loot_table.roll(game_rng.as_mut()))
+            .unwrap_or_default();
+        for (index, item) in drops.into_iter().enumerate() {
+            // an enemy only dies once, its iid tells the drops apart from everything else
+            let iid = iid.map(|iid| EntityIid::new(format!("{}-loot-{index}", iid.as_str())));
+            spawn_item(
+                &mut commands,
+                item,
+                *coords,
+                iid,
+                parent.map(|parent| parent.get()),
+            );
         }
+
+        enemy_died_ew.send(EnemyDied {
+            enemy: entity,
+            coords: *coords,
+            killer: last_hit_by.map(|last_hit_by| last_hit_by.0),
+            experience: experience.map_or(0, |experience| experience.0),
+        });
+
+        // the health bar goes with the enemy, the body stays for the death animation. Without
+        // health it is no target for projectiles or status effects anymore.
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .remove::<(Enemy, Actor, Collider, EnemyAttacking, Health)>()
+            .insert(Dying(Timer::from_seconds(DEATH_DURATION, TimerMode::Once)));
     }
 }
 
-fn despawn_dead_enemies(mut commands: Commands, enemies_q: Query<(Entity, &Health), With<Enemy>>) {
-    for (entity, health) in enemies_q.iter() {
-        if health.current_health <= 0 {
+fn despawn_dying_enemies(
+    mut commands: Commands,
+    mut dying_q: Query<(Entity, &mut Dying)>,
+    time: Res<Time>,
+) {
+    for (entity, mut dying) in dying_q.iter_mut() {
+        if dying.0.tick(time.delta()).finished() {
             commands.entity(entity).despawn_recursive();
         }
     }
@@ -1,45 +1,85 @@
-use bevy::{
-    color::palettes::css,
-    prelude::*,
-    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
-};
+use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_ecs_ldtk::GridCoords;
 
 use crate::{
-    events::TurnOver,
+    combat::{
+        stats::{BaseStats, DamageKind, Stats},
+        status::{AppliedStatus, StatusEffects, StatusKind},
+        HitEvent,
+    },
     grid::Collider,
     ldtk::LevelWalls,
-    player::{Player, PlayerAction},
-    AnimationTimer, AppState, Health, IdleAnimationTimer, IndeciesIter, ACTION_DELAY,
+    player::Player,
+    rng::GameRng,
+    turn::{ActionCost, Actor, TurnQueue, TurnSet},
+    AnimationTimer, AppState, Health, IdleAnimationTimer, IndeciesIter, ACTION_DELAY, LEVEL_LOADED,
 };
 
-use super::{health_bar::HealthBarMaterial, AttackRange, Enemy, EnemyBehaviorState, HealthBar};
+use super::{
+    loot::{Loot, LootAssets},
+    nearest_player, AttackDamage, AttackRange, Cornered, Dying, Enemy, EnemyAttacking,
+    EnemyBehaviorState, ExperienceReward, PatrolRoute, FLEE_REGENERATION,
+};
 
 pub struct SlimePlugin;
 
 impl Plugin for SlimePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(LEVEL_LOADED, patch_slime)
+            .add_systems(
+                FixedUpdate,
+                move_slime
+                    .in_set(TurnSet::Enemy)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .register_type::<SlimeAnimationState>();
+    }
+}
+
+/// Sprites and animations of slimes, left out when running headless.
+pub struct SlimeAnimationPlugin;
+
+impl Plugin for SlimeAnimationPlugin {
     fn build(&self, app: &mut App) {
         app.add_loading_state(
             LoadingState::new(AppState::Loading)
                 .continue_to_state(AppState::InGame)
                 .load_collection::<SlimeAnimation>(),
         )
-        .add_systems(OnEnter(AppState::InGame), patch_slime)
+        .add_systems(LEVEL_LOADED, patch_slime_sprites)
         .add_systems(
             FixedUpdate,
             (
                 update_slime_idle_animation,
                 update_slime_walking_animation,
+                update_slime_attack_animation,
+                start_slime_death_animation,
+                update_slime_death_animation,
                 update_slime_atlas_index,
-                move_slime,
             )
                 .run_if(in_state(AppState::InGame)),
-        )
-        .register_type::<SlimeAnimationState>();
+        );
     }
 }
 
+const SLIME_STATS: Stats = Stats {
+    attack: 0,
+    defense: 2,
+    spell_power: 0,
+    max_health: 100,
+};
+const SLIME_EXPERIENCE: u32 = 40;
+// slimes are venomous, and a cornered one clings to whoever it bites
+const SLIME_BITE: AppliedStatus = AppliedStatus {
+    kind: StatusKind::Poison,
+    turns: 3,
+};
+const CORNERED_SLIME_BITE: AppliedStatus = AppliedStatus {
+    kind: StatusKind::Slow,
+    turns: 2,
+};
+
 #[derive(Default, Component, Reflect)]
 pub struct Slime;
 
@@ -48,12 +88,16 @@ pub enum SlimeAnimationState {
     #[default]
     Idle,
     Walking,
+    Attacking,
+    Dying,
 }
 
 #[derive(Component)]
 struct SlimeAnimationIndecies {
     idle: IndeciesIter,
     walking: IndeciesIter,
+    attacking: IndeciesIter,
+    dying: IndeciesIter,
 }
 
 #[derive(AssetCollection, Resource)]
@@ -74,16 +118,39 @@ struct SlimeAnimation {
 }
 
 fn patch_slime(
+    mut commands: Commands,
+    slime_query: Query<Entity, With<Slime>>,
+    loot_assets: Res<LootAssets>,
+) {
+    for entity in &slime_query {
+        commands.entity(entity).insert((
+            SlimeAnimationState::default(),
+            Enemy::default(),
+            Collider::default(),
+            AttackRange::default(),
+            AttackDamage::default(),
+            Health::default(),
+            BaseStats(SLIME_STATS),
+            SLIME_STATS,
+            StatusEffects::default(),
+            Actor::default(),
+            Loot(loot_assets.slime.clone()),
+            ExperienceReward(SLIME_EXPERIENCE),
+        ));
+    }
+}
+
+fn patch_slime_sprites(
     mut commands: Commands,
     asset: Res<SlimeAnimation>,
     mut slime_query: Query<(Entity, &mut TextureAtlas, &mut Handle<Image>), With<Slime>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut health_bar_materials: ResMut<Assets<HealthBarMaterial>>,
 ) {
     for (entity, mut atlas, mut texture) in &mut slime_query {
         let slime_animation_indices = SlimeAnimationIndecies {
             idle: vec![0, 1].into(),
             walking: vec![2, 3, 4, 5].into(),
+            attacking: vec![6, 7, 8, 7].into(),
+            dying: vec![10, 11, 12, 13, 14].into(),
         };
 
         atlas.layout = asset.layout.clone();
@@ -95,31 +162,7 @@ fn patch_slime(
             )),
             IdleAnimationTimer(Timer::from_seconds(1.0, TimerMode::Repeating)),
             slime_animation_indices,
-            SlimeAnimationState::default(),
-            Enemy::default(),
-            Collider::default(),
-            AttackRange::default(),
-            Health::default(),
         ));
-
-        let healt_bar = commands
-            .spawn((
-                HealthBar,
-                MaterialMesh2dBundle {
-                    mesh: Mesh2dHandle(meshes.add(Rectangle::new(10.0, 1.0))),
-                    material: health_bar_materials.add(HealthBarMaterial {
-                        foreground_color: css::GREEN.into(),
-                        background_color: css::RED.into(),
-                        percent: 1.,
-                    }),
-                    transform: Transform::from_xyz(0., 8., 0.),
-                    visibility: Visibility::Hidden,
-                    ..default()
-                },
-            ))
-            .id();
-
-        commands.entity(entity).add_child(healt_bar);
     }
 }
 
@@ -129,16 +172,76 @@ fn update_slime_walking_animation(
             &mut SlimeAnimationIndecies,
             &mut AnimationTimer,
             &mut TextureAtlas,
-            &SlimeAnimationState,
+            &mut SlimeAnimationState,
         ),
         With<Slime>,
     >,
     time: Res<Time>,
 ) {
-    for (mut slime_indices, mut timer, mut atlas, slime_state) in &mut query {
+    for (mut slime_indices, mut timer, mut atlas, mut slime_state) in &mut query {
         timer.tick(time.delta());
         if timer.just_finished() && *slime_state == SlimeAnimationState::Walking {
             atlas.index = slime_indices.walking.next().expect("looping iterator");
+
+            // a step is a single jump, go back to idling until the next turn
+            if slime_indices.walking.cycle_finished() {
+                slime_indices.walking.reset();
+                *slime_state = SlimeAnimationState::Idle;
+            }
+        }
+    }
+}
+
+fn update_slime_attack_animation(
+    mut query: Query<
+        (
+            &mut SlimeAnimationIndecies,
+            &AnimationTimer,
+            &mut TextureAtlas,
+            &mut SlimeAnimationState,
+        ),
+        With<Slime>,
+    >,
+) {
+    for (mut slime_indices, timer, mut atlas, mut slime_state) in &mut query {
+        // the walking animation ticks the shared timer
+        if timer.just_finished() && *slime_state == SlimeAnimationState::Attacking {
+            atlas.index = slime_indices.attacking.next().expect("looping iterator");
+
+            if slime_indices.attacking.cycle_finished() {
+                slime_indices.attacking.reset();
+                *slime_state = SlimeAnimationState::Idle;
+            }
+        }
+    }
+}
+
+fn start_slime_death_animation(
+    mut query: Query<&mut SlimeAnimationState, (Added<Dying>, With<Slime>)>,
+) {
+    for mut slime_state in &mut query {
+        *slime_state = SlimeAnimationState::Dying;
+    }
+}
+
+fn update_slime_death_animation(
+    mut query: Query<
+        (
+            &mut SlimeAnimationIndecies,
+            &AnimationTimer,
+            &mut TextureAtlas,
+            &SlimeAnimationState,
+        ),
+        With<Slime>,
+    >,
+) {
+    for (mut slime_indices, timer, mut atlas, slime_state) in &mut query {
+        // the walking animation ticks the shared timer, the last frame stays until the despawn
+        if timer.just_finished()
+            && *slime_state == SlimeAnimationState::Dying
+            && !slime_indices.dying.cycle_finished()
+        {
+            atlas.index = slime_indices.dying.next().expect("looping iterator");
         }
     }
 }
@@ -183,74 +286,163 @@ fn update_slime_atlas_index(
 
 #[allow(clippy::type_complexity)]
 fn move_slime(
+    mut commands: Commands,
     mut query: Query<
-        (&mut GridCoords, &mut SlimeAnimationState, &Enemy),
+        (
+            &mut GridCoords,
+            &mut SlimeAnimationState,
+            &mut Enemy,
+            &mut Health,
+            Option<&mut PatrolRoute>,
+            &AttackDamage,
+            &StatusEffects,
+            Has<EnemyAttacking>,
+            Has<Cornered>,
+        ),
         (With<Slime>, Without<Player>),
     >,
-    player_pos: Query<&GridCoords, With<Player>>,
+    players: Query<(Entity, &GridCoords), With<Player>>,
     level_walls: Res<LevelWalls>,
     colliders: Query<&Collider>,
-    mut turn_over_er: EventReader<TurnOver>,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut game_rng: ResMut<GameRng>,
 ) {
-    let event = turn_over_er.read().next();
-    if event.is_none() {
+    let living_players: Vec<(Entity, GridCoords)> = players
+        .iter()
+        .filter(|(entity, _)| turn_queue.contains(*entity))
+        .map(|(entity, coords)| (entity, *coords))
+        .collect();
+
+    // without players in the queue, the slimes would take turns forever
+    if living_players.is_empty() {
         return;
     }
 
-    match event.unwrap().0 {
-        PlayerAction::Idle => {
-            for (_, mut slime_animation, _) in query.iter_mut() {
-                if *slime_animation != SlimeAnimationState::Idle {
-                    *slime_animation = SlimeAnimationState::Idle;
-                }
-            }
+    let mut occupied_coords: Vec<GridCoords> = Vec::new();
+
+    for collider in colliders.iter() {
+        occupied_coords.extend(collider.get_occupied_coords());
+    }
+
+    while let Some(current) = turn_queue.current() {
+        let Ok((
+            mut coords,
+            mut slime_animation,
+            mut enemy,
+            mut health,
+            patrol_route,
+            attack_damage,
+            status_effects,
+            attacking,
+            cornered,
+        )) = query.get_mut(current)
+        else {
+            break;
+        };
+
+        if status_effects.has(StatusKind::Stun) {
+            turn_queue.end_turn(current, ActionCost::Wait);
+            continue;
         }
-        PlayerAction::Walking => {
-            let player_pos = if let Ok(player_pos) = player_pos.get_single() {
-                *player_pos
-            } else {
-                return;
-            };
 
-            let mut occupied_coords: Vec<GridCoords> = Vec::new();
+        // cornered slimes keep recovering while they fight, or they would stay cornered for good
+        if enemy.behavior_state == EnemyBehaviorState::Fleeing || cornered {
+            health.current_health =
+                (health.current_health + FLEE_REGENERATION).min(health.max_health);
+        }
 
-            for collider in colliders.iter() {
-                occupied_coords.extend(collider.get_occupied_coords());
-            }
+        let Some((player_entity, player_pos)) = nearest_player(&coords, living_players.iter())
+        else {
+            break;
+        };
 
-            for (mut coords, mut slime_animation, enemy) in query.iter_mut() {
-                let direction = match enemy.behavior_state {
-                    EnemyBehaviorState::Idle => enemy.move_towards_player(
-                        &player_pos,
-                        &coords,
-                        &level_walls,
-                        &occupied_coords,
-                    ),
-                    EnemyBehaviorState::Fleeing => todo!(),
-                    EnemyBehaviorState::Pursuing => {
-                        let direction = enemy.move_towards_player(
+        // the players the slime is not going after are in its way
+        let blocked_coords: Vec<GridCoords> = occupied_coords
+            .iter()
+            .copied()
+            .chain(
+                living_players
+                    .iter()
+                    .filter(|(entity, _)| *entity != player_entity)
+                    .map(|(_, coords)| *coords),
+            )
+            .collect();
+
+        if attacking && enemy.behavior_state == EnemyBehaviorState::Pursuing {
+            *slime_animation = SlimeAnimationState::Attacking;
+            commands.trigger(HitEvent {
+                target: player_entity,
+                origin: current,
+                ability: None,
+                damage: attack_damage.0,
+                kind: DamageKind::Physical,
+                status: Some(if cornered {
+                    CORNERED_SLIME_BITE
+                } else {
+                    SLIME_BITE
+                }),
+            });
+            turn_queue.end_turn(current, ActionCost::Attack);
+            continue;
+        }
+
+        let direction = match enemy.behavior_state {
+            EnemyBehaviorState::Idle => enemy.move_towards_player(
+                &player_pos,
+                &coords,
+                &level_walls,
+                &blocked_coords,
+                game_rng.as_mut(),
+            ),
+            EnemyBehaviorState::Fleeing => {
+                match enemy.flee(&player_pos, &coords, &level_walls, &blocked_coords) {
+                    Some(direction) => direction,
+                    None => {
+                        // nowhere left to run, turn around and fight
+                        commands.entity(current).insert(Cornered);
+                        enemy.behavior_state = EnemyBehaviorState::Pursuing;
+                        enemy.move_towards_player(
                             &player_pos,
                             &coords,
                             &level_walls,
-                            &occupied_coords,
-                        );
-                        GridCoords::new(direction.x, direction.y)
-                    }
-                    EnemyBehaviorState::Patrolling => todo!(),
-                };
-
-                if direction != GridCoords::new(0, 0) {
-                    *slime_animation = SlimeAnimationState::Walking;
-                    let destination = *coords + direction;
-                    if !level_walls.in_wall(&destination) {
-                        let prev_position = *coords;
-                        *coords = destination;
-                        occupied_coords.retain(|&x| x != prev_position);
-                        occupied_coords.push(destination);
+                            &blocked_coords,
+                            game_rng.as_mut(),
+                        )
                     }
                 }
             }
+            EnemyBehaviorState::Pursuing => {
+                let direction = enemy.move_towards_player(
+                    &player_pos,
+                    &coords,
+                    &level_walls,
+                    &blocked_coords,
+                    game_rng.as_mut(),
+                );
+                GridCoords::new(direction.x, direction.y)
+            }
+            EnemyBehaviorState::Patrolling => match patrol_route {
+                Some(mut patrol_route) => {
+                    patrol_route.next_step(&coords, &level_walls, &blocked_coords)
+                }
+                None => GridCoords::new(0, 0),
+            },
+        };
+
+        // rooted slimes still bite, but stay where they are
+        if direction == GridCoords::new(0, 0) || status_effects.has(StatusKind::Root) {
+            turn_queue.end_turn(current, ActionCost::Wait);
+            continue;
         }
-        PlayerAction::Combat => (),
-    };
+
+        *slime_animation = SlimeAnimationState::Walking;
+        let destination = *coords + direction;
+        if !level_walls.in_wall(&destination) {
+            let prev_position = *coords;
+            *coords = destination;
+            occupied_coords.retain(|&x| x != prev_position);
+            occupied_coords.push(destination);
+        }
+        turn_queue.end_turn(current, ActionCost::Move);
+    }
 }
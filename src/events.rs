@@ -1,17 +1,61 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::player::PlayerAction;
+use crate::save::SavedCoords;
 
 pub struct EventsPlugin;
 
 impl Plugin for EventsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<CombatEvent>().add_event::<TurnOver>();
+        app.add_event::<CombatEvent>()
+            .add_event::<TurnEnded>()
+            .add_event::<RestartRun>()
+            .add_event::<SaveRun>()
+            .add_event::<LoadRun>()
+            .add_event::<PlayerActed>();
     }
 }
 
+/// Sent once an actor has spent its turn in the [`crate::turn::TurnQueue`].
 #[derive(Event)]
-pub struct TurnOver(pub PlayerAction);
+pub struct TurnEnded(pub Entity);
 
 #[derive(Event)]
 pub struct CombatEvent(pub bool);
+
+/// Starts a new run from the first level, sent from the game over screen.
+#[derive(Event)]
+pub struct RestartRun;
+
+/// Writes the current run to the save file.
+#[derive(Event)]
+pub struct SaveRun;
+
+/// Replaces the current run with the one in the save file.
+#[derive(Event)]
+pub struct LoadRun;
+
+/// Sent with the action a player spent its turn on, recordings are made from these.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PlayerActed {
+    pub player: Entity,
+    pub action: PlayerTurnAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerTurnAction {
+    /// A step in a direction, also sent when the step was blocked.
+    Move(SavedCoords),
+    Cast {
+        slot: usize,
+        target: SavedCoords,
+    },
+    /// Using the item in an inventory slot.
+    UseItem {
+        slot: usize,
+    },
+    /// Putting the item in an inventory slot down.
+    DropItem {
+        slot: usize,
+    },
+}
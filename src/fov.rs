@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::GridCoords;
+
+use crate::{
+    enemy::Enemy,
+    grid::los::field_of_view,
+    item::Item,
+    ldtk::{Floor, LevelWalls},
+    player::Player,
+    AppState, GRID_SIZE,
+};
+
+pub struct FovPlugin;
+
+impl Plugin for FovPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (add_field_of_view, update_field_of_view)
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// Darkens floor tiles outside the field of view and hides the enemies standing on them, as well
+/// as items nobody has seen yet. Left out when running headless.
+pub struct FogOfWarPlugin;
+
+impl Plugin for FogOfWarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                spawn_fog_tiles,
+                update_fog_tiles,
+                hide_enemies_outside_fov,
+                hide_unexplored_items,
+            )
+                .after(update_field_of_view)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+// distance in tiles the player can see
+pub const FOV_RADIUS: i32 = 8;
+
+// z-index of the fog relative to the floor tile, above the grid overlays
+const FOG_Z_INDEX: f32 = 7.0;
+const REMEMBERED_FOG_ALPHA: f32 = 0.6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TileVisibility {
+    Unseen,
+    Remembered,
+    Visible,
+}
+
+/// Tiles a player currently sees and every tile it has seen on the current level.
+#[derive(Component, Debug)]
+pub struct FieldOfView {
+    pub radius: i32,
+    pub visible: HashSet<GridCoords>,
+    pub explored: HashSet<GridCoords>,
+}
+
+impl Default for FieldOfView {
+    fn default() -> Self {
+        Self {
+            radius: FOV_RADIUS,
+            visible: HashSet::new(),
+            explored: HashSet::new(),
+        }
+    }
+}
+
+impl FieldOfView {
+    pub fn tile_visibility(&self, coords: &GridCoords) -> TileVisibility {
+        if self.visible.contains(coords) {
+            TileVisibility::Visible
+        } else if self.explored.contains(coords) {
+            TileVisibility::Remembered
+        } else {
+            TileVisibility::Unseen
+        }
+    }
+
+    pub fn update(&mut self, origin: &GridCoords, level_walls: &LevelWalls) {
+        self.visible = field_of_view(origin, self.radius, level_walls);
+        self.explored.extend(self.visible.iter().copied());
+    }
+}
+
+#[derive(Component)]
+struct FogTile;
+
+fn add_field_of_view(
+    mut commands: Commands,
+    players: Query<Entity, (With<Player>, Without<FieldOfView>)>,
+) {
+    for entity in players.iter() {
+        commands.entity(entity).insert(FieldOfView::default());
+    }
+}
+
+fn update_field_of_view(
+    mut players: Query<(Ref<GridCoords>, &mut FieldOfView), With<Player>>,
+    level_walls: Res<LevelWalls>,
+) {
+    for (coords, mut field_of_view) in players.iter_mut() {
+        // the field of view only changes once the player moved to a new tile
+        if coords.is_changed() || field_of_view.is_added() || level_walls.is_changed() {
+            field_of_view.update(&coords, &level_walls);
+        }
+    }
+}
+
+fn spawn_fog_tiles(mut commands: Commands, floor: Query<Entity, Added<Floor>>) {
+    for entity in floor.iter() {
+        let fog_id = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::BLACK,
+                        custom_size: Some(Vec2::splat(GRID_SIZE as f32)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, FOG_Z_INDEX),
+                    ..default()
+                },
+                FogTile,
+            ))
+            .id();
+
+        commands.entity(entity).add_child(fog_id);
+    }
+}
+
+fn update_fog_tiles(
+    players: Query<Ref<FieldOfView>, With<Player>>,
+    floor: Query<&GridCoords, With<Floor>>,
+    mut fog_tiles: Query<(&Parent, &mut Sprite, &mut Visibility, Ref<FogTile>)>,
+) {
+    let fov_changed = players
+        .iter()
+        .any(|field_of_view| field_of_view.is_changed());
+
+    for (parent, mut sprite, mut visibility, fog_tile) in fog_tiles.iter_mut() {
+        if !fov_changed && !fog_tile.is_added() {
+            continue;
+        }
+
+        let Ok(coords) = floor.get(parent.get()) else {
+            continue;
+        };
+
+        // with several players, a tile is as visible as the best view any of them has
+        let tile_visibility = players
+            .iter()
+            .map(|field_of_view| field_of_view.tile_visibility(coords))
+            .max()
+            .unwrap_or(TileVisibility::Unseen);
+
+        match tile_visibility {
+            TileVisibility::Visible => *visibility = Visibility::Hidden,
+            TileVisibility::Remembered => {
+                *visibility = Visibility::Inherited;
+                sprite.color = Color::BLACK.with_alpha(REMEMBERED_FOG_ALPHA);
+            }
+            TileVisibility::Unseen => {
+                *visibility = Visibility::Inherited;
+                sprite.color = Color::BLACK;
+            }
+        }
+    }
+}
+
+fn hide_enemies_outside_fov(
+    players: Query<&FieldOfView, With<Player>>,
+    mut enemies: Query<(&GridCoords, &mut Visibility), With<Enemy>>,
+) {
+    if players.is_empty() {
+        return;
+    }
+
+    for (coords, mut visibility) in enemies.iter_mut() {
+        let visible = players
+            .iter()
+            .any(|field_of_view| field_of_view.visible.contains(coords));
+
+        visibility.set_if_neq(if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+// items don't move, once seen they are remembered like the floor they lie on
+fn hide_unexplored_items(
+    players: Query<&FieldOfView, With<Player>>,
+    mut items: Query<(&GridCoords, &mut Visibility), With<Item>>,
+) {
+    if players.is_empty() {
+        return;
+    }
+
+    for (coords, mut visibility) in items.iter_mut() {
+        let explored = players
+            .iter()
+            .any(|field_of_view| field_of_view.explored.contains(coords));
+
+        visibility.set_if_neq(if explored {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walls(wall_locations: &[(i32, i32)]) -> LevelWalls {
+        LevelWalls {
+            wall_locations: wall_locations
+                .iter()
+                .map(|(x, y)| GridCoords::new(*x, *y))
+                .collect(),
+            level_width: 20,
+            level_height: 20,
+        }
+    }
+
+    fn field_of_view(radius: i32) -> FieldOfView {
+        FieldOfView {
+            radius,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn view_is_limited_to_the_radius() {
+        let mut field_of_view = field_of_view(3);
+        let origin = GridCoords::new(10, 10);
+
+        field_of_view.update(&origin, &walls(&[]));
+
+        assert!(field_of_view.visible.contains(&origin));
+        assert!(field_of_view.visible.contains(&GridCoords::new(13, 10)));
+        assert!(!field_of_view.visible.contains(&GridCoords::new(14, 10)));
+        // the corners of the square are outside the circle
+        assert!(!field_of_view.visible.contains(&GridCoords::new(13, 13)));
+    }
+
+    #[test]
+    fn walls_are_seen_but_hide_what_is_behind_them() {
+        let mut field_of_view = field_of_view(5);
+
+        field_of_view.update(&GridCoords::new(10, 10), &walls(&[(12, 10)]));
+
+        assert!(field_of_view.visible.contains(&GridCoords::new(12, 10)));
+        assert!(!field_of_view.visible.contains(&GridCoords::new(13, 10)));
+        assert!(field_of_view.visible.contains(&GridCoords::new(10, 13)));
+    }
+
+    #[test]
+    fn tiles_seen_before_are_remembered() {
+        let mut field_of_view = field_of_view(2);
+        let level_walls = walls(&[]);
+        let first_tile = GridCoords::new(3, 3);
+
+        field_of_view.update(&first_tile, &level_walls);
+        field_of_view.update(&GridCoords::new(10, 10), &level_walls);
+
+        assert_eq!(
+            field_of_view.tile_visibility(&first_tile),
+            TileVisibility::Remembered
+        );
+        assert_eq!(
+            field_of_view.tile_visibility(&GridCoords::new(10, 11)),
+            TileVisibility::Visible
+        );
+        assert_eq!(
+            field_of_view.tile_visibility(&GridCoords::new(17, 17)),
+            TileVisibility::Unseen
+        );
+    }
+}
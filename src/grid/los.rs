@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+
+use bevy_ecs_ldtk::GridCoords;
+
+use crate::ldtk::LevelWalls;
+
+/// Tiles on the Bresenham line from `from` to `to`, both ends included.
+pub fn line(from: GridCoords, to: GridCoords) -> Vec<GridCoords> {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+
+    let mut tiles = Vec::with_capacity(dx.max(-dy) as usize + 1);
+    let mut current = from;
+    let mut error = dx + dy;
+
+    loop {
+        tiles.push(current);
+        if current == to {
+            return tiles;
+        }
+
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            current.x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            current.y += step_y;
+        }
+    }
+}
+
+/// Whether `to` can be seen from `from`, i.e. no wall is on the line between them. Bresenham
+/// lines aren't symmetric, so the result can differ when swapping `from` and `to`.
+pub fn has_line_of_sight(from: &GridCoords, to: &GridCoords, level_walls: &LevelWalls) -> bool {
+    line(*from, *to)
+        .iter()
+        .all(|coords| !level_walls.in_wall(coords))
+}
+
+/// Number of king moves between two tiles, the distance ability ranges are measured in.
+pub fn tile_distance(from: &GridCoords, to: &GridCoords) -> i32 {
+    (to.x - from.x).abs().max((to.y - from.y).abs())
+}
+
+/// Tiles visible from `origin` within a circle of `radius`. Walls are visible themselves but
+/// block the view of everything behind them.
+pub fn field_of_view(
+    origin: &GridCoords,
+    radius: i32,
+    level_walls: &LevelWalls,
+) -> HashSet<GridCoords> {
+    let mut visible = HashSet::new();
+
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            if x * x + y * y > radius * radius {
+                continue;
+            }
+
+            let tile = GridCoords::new(origin.x + x, origin.y + y);
+            let line = line(*origin, tile);
+            if line[..line.len() - 1]
+                .iter()
+                .all(|coords| !level_walls.in_wall(coords))
+            {
+                visible.insert(tile);
+            }
+        }
+    }
+
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walls(wall_locations: &[(i32, i32)]) -> LevelWalls {
+        LevelWalls {
+            wall_locations: wall_locations
+                .iter()
+                .map(|(x, y)| GridCoords::new(*x, *y))
+                .collect(),
+            level_width: 10,
+            level_height: 10,
+        }
+    }
+
+    #[test]
+    fn line_includes_both_ends() {
+        let from = GridCoords::new(1, 1);
+        let to = GridCoords::new(5, 3);
+
+        let tiles = line(from, to);
+
+        assert_eq!(tiles.first(), Some(&from));
+        assert_eq!(tiles.last(), Some(&to));
+        // one tile per step along the longer axis
+        assert_eq!(tiles.len(), 5);
+    }
+
+    #[test]
+    fn line_to_itself_is_one_tile() {
+        let coords = GridCoords::new(3, 3);
+
+        assert_eq!(line(coords, coords), vec![coords]);
+    }
+
+    #[test]
+    fn wall_in_between_blocks_the_view() {
+        let level_walls = walls(&[(3, 1)]);
+
+        assert!(!has_line_of_sight(
+            &GridCoords::new(1, 1),
+            &GridCoords::new(5, 1),
+            &level_walls
+        ));
+    }
+
+    #[test]
+    fn wall_beside_the_line_does_not_block_the_view() {
+        let level_walls = walls(&[(3, 2)]);
+
+        assert!(has_line_of_sight(
+            &GridCoords::new(1, 1),
+            &GridCoords::new(5, 1),
+            &level_walls
+        ));
+    }
+
+    #[test]
+    fn straight_and_diagonal_lines_are_symmetric() {
+        let level_walls = walls(&[(4, 4), (2, 6)]);
+        let from = GridCoords::new(2, 2);
+
+        for to in [
+            GridCoords::new(6, 2),
+            GridCoords::new(2, 8),
+            GridCoords::new(6, 6),
+            GridCoords::new(5, 5),
+        ] {
+            assert_eq!(
+                has_line_of_sight(&from, &to, &level_walls),
+                has_line_of_sight(&to, &from, &level_walls),
+                "from {from:?} to {to:?}"
+            );
+        }
+        assert!(!has_line_of_sight(
+            &from,
+            &GridCoords::new(6, 6),
+            &level_walls
+        ));
+        assert!(!has_line_of_sight(
+            &GridCoords::new(6, 6),
+            &from,
+            &level_walls
+        ));
+    }
+
+    #[test]
+    fn tiles_outside_the_level_block_the_view() {
+        let level_walls = walls(&[]);
+
+        assert!(!has_line_of_sight(
+            &GridCoords::new(0, 0),
+            &GridCoords::new(-1, 0),
+            &level_walls
+        ));
+    }
+
+    #[test]
+    fn tile_distance_counts_diagonal_steps_once() {
+        assert_eq!(
+            tile_distance(&GridCoords::new(0, 0), &GridCoords::new(3, 2)),
+            3
+        );
+        assert_eq!(
+            tile_distance(&GridCoords::new(4, 1), &GridCoords::new(1, 1)),
+            3
+        );
+    }
+}
@@ -2,14 +2,20 @@ use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use leafwing_input_manager::prelude::ActionState;
 use pathfinding::prelude::astar;
+use std::collections::{HashMap, VecDeque};
 
 use crate::camera::MainCamera;
 
 use crate::get_single;
 use crate::input::PlayerInputAction;
-use crate::ldtk::{Floor, Grid, LevelWalls, LosGrid, Stair, Wall};
+use crate::ldtk::{Floor, Grid, LevelWalls, LosGrid, Stair};
 use crate::ui::game_cursor::CursorDirection;
-use crate::{player::Player, AppState, GameplaySet, GRID_SIZE};
+use crate::{
+    player::{Player, PrimaryPlayer},
+    AppState, GameplaySet, GRID_SIZE,
+};
+
+pub mod los;
 
 pub struct GridPlugin;
 
@@ -19,17 +25,24 @@ impl Plugin for GridPlugin {
             Update,
             (
                 translate_grid_coords_entities,
-                cache_wall_locations,
                 check_stairs,
                 update_colliders,
-                display_los_grid,
             )
                 .in_set(GameplaySet::InputSet),
         )
-        .add_systems(Update, (toggel_grid).run_if(in_state(AppState::InGame)))
-        .register_type::<Collider>()
-        .add_systems(OnExit(AppState::Loading), (spawn_grid, spawn_los_grid))
-        .insert_resource(GridToggled(false));
+        .register_type::<Collider>();
+    }
+}
+
+/// The toggleable yellow grid and the red line of sight overlay, left out when running headless.
+pub struct GridOverlayPlugin;
+
+impl Plugin for GridOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, display_los_grid.in_set(GameplaySet::InputSet))
+            .add_systems(Update, (toggel_grid).run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::Loading), (spawn_grid, spawn_los_grid))
+            .insert_resource(GridToggled(false));
     }
 }
 
@@ -119,6 +132,10 @@ impl GridPosition {
                     }
                 }
 
+                if level_walls.cuts_corner(coords, GridCoords::new(x, y)) {
+                    continue;
+                }
+
                 if !level_walls.wall_locations.contains(&new_coords) {
                     successors.push(Successor {
                         coords: GridPosition(new_coords),
@@ -131,6 +148,35 @@ impl GridPosition {
         successors
     }
 
+    /// Path distance to every tile that can be reached within `max_distance` steps.
+    pub fn distances(
+        &self,
+        level_walls: &LevelWalls,
+        max_distance: u32,
+    ) -> HashMap<GridCoords, u32> {
+        let mut distances = HashMap::from([(self.0, 0)]);
+        let mut frontier = VecDeque::from([self.0]);
+
+        while let Some(coords) = frontier.pop_front() {
+            let distance = distances[&coords];
+            if distance >= max_distance {
+                continue;
+            }
+
+            for successor in self.successors(&coords, level_walls, None) {
+                let next = successor.coords.0;
+                if level_walls.in_wall(&next) || distances.contains_key(&next) {
+                    continue;
+                }
+
+                distances.insert(next, distance + successor.cost);
+                frontier.push_back(next);
+            }
+        }
+
+        distances
+    }
+
     pub fn heuristic(&self, goal: &GridCoords) -> u32 {
         let dx = (self.0.x - goal.x).unsigned_abs();
         let dy = (self.0.y - goal.y).unsigned_abs();
@@ -181,46 +227,17 @@ fn translate_grid_coords_entities(
     }
 }
 
-fn cache_wall_locations(
-    mut level_walls: ResMut<LevelWalls>,
-    mut level_events: EventReader<LevelEvent>,
-    walls: Query<&GridCoords, With<Wall>>,
-    ldtk_project_entities: Query<&Handle<LdtkProject>>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>,
-) {
-    for level_event in level_events.read() {
-        if let LevelEvent::Spawned(level_iid) = level_event {
-            let ldtk_project = ldtk_project_assets
-                .get(ldtk_project_entities.single())
-                .expect("LdtkProject should be loaded when level is spawned");
-            let level = ldtk_project
-                .get_raw_level_by_iid(level_iid.get())
-                .expect("spawned level should exist in project");
-
-            let wall_locations = walls.iter().copied().collect();
-
-            let new_level_walls = LevelWalls {
-                wall_locations,
-                level_width: level.px_wid / GRID_SIZE,
-                level_height: level.px_hei / GRID_SIZE,
-            };
-
-            *level_walls = new_level_walls;
-        }
-    }
-}
-
 fn check_stairs(
     players: Query<&GridCoords, (With<Player>, Changed<GridCoords>)>,
     level_selection: ResMut<LevelSelection>,
     stair: Query<&GridCoords, With<Stair>>,
     mut next_state: ResMut<NextState<AppState>>,
 ) {
-    if players
-        .iter()
-        .zip(stair.iter())
-        .any(|(player_grid_coords, stairs_grid_coords)| player_grid_coords == stairs_grid_coords)
-    {
+    if players.iter().any(|player_grid_coords| {
+        stair
+            .iter()
+            .any(|stairs_grid_coords| player_grid_coords == stairs_grid_coords)
+    }) {
         let indices = match level_selection.into_inner() {
             LevelSelection::Indices(indices) => indices,
             _ => panic!("level selection should always be Indices in this game"),
@@ -264,26 +281,30 @@ fn toggel_grid(
     query: Query<&ActionState<PlayerInputAction>, With<Player>>,
     mut grid_toggled: ResMut<GridToggled>,
 ) {
-    if let Ok(action_state) = query.get_single() {
-        if action_state.just_pressed(&PlayerInputAction::Tab) {
-            let mut toggled_to_visible = false;
-            for mut visibility in grid.iter_mut() {
-                *visibility = if *visibility == Visibility::Hidden {
-                    toggled_to_visible = true;
-                    Visibility::Visible
-                } else {
-                    toggled_to_visible = false;
-                    Visibility::Hidden
-                };
-            }
+    if query.is_empty() {
+        warn!("No Player entity found in toggel_grid system");
+        return;
+    }
 
-            grid_toggled.0 = toggled_to_visible;
+    // the grid is shared, any player can toggle it
+    if query
+        .iter()
+        .any(|action_state| action_state.just_pressed(&PlayerInputAction::Tab))
+    {
+        let mut toggled_to_visible = false;
+        for mut visibility in grid.iter_mut() {
+            *visibility = if *visibility == Visibility::Hidden {
+                toggled_to_visible = true;
+                Visibility::Visible
+            } else {
+                toggled_to_visible = false;
+                Visibility::Hidden
+            };
+        }
 
+        grid_toggled.0 = toggled_to_visible;
 
-            info!("Toggled grid visibility");
-        }
-    } else {
-        warn!("No Player entity found in toggel_grid system");
+        info!("Toggled grid visibility");
     }
 }
 
@@ -309,12 +330,12 @@ fn spawn_los_grid(
 
 fn display_los_grid(
     floor: Query<(&GridCoords, Option<&Children>), With<Floor>>,
-    player_grid: Query<&GridCoords, With<Player>>,
+    player_grid: Query<&GridCoords, With<PrimaryPlayer>>,
     cursor_direction: Res<CursorDirection>,
     grid_toggled: Res<GridToggled>,
-       mut visibility_param_set: ParamSet<(
+    mut visibility_param_set: ParamSet<(
         Query<&mut Visibility, With<LosGrid>>, // ParamSet 0: Query for red grid
-        Query<&mut Visibility, With<Grid>>,     // ParamSet 1: Query for yellow grid
+        Query<&mut Visibility, With<Grid>>,    // ParamSet 1: Query for yellow grid
     )>,
 ) {
     // If the grid is not toggled, hide all los grids
@@ -0,0 +1,204 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{prelude::*, state::app::StatesPlugin, time::TimeUpdateStrategy};
+use bevy_ecs_ldtk::{ldtk::LdtkJson, prelude::*};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    combat::CombatPlugin,
+    dungeon::{spawn_level_layout, AuthoredLevels, DungeonPlugin},
+    enemy::{slime::SlimePlugin, EnemyPlugin},
+    events::EventsPlugin,
+    fov::FovPlugin,
+    grid::GridPlugin,
+    input::PlayerInputAction,
+    item::ItemPlugin,
+    ldtk::{LevelFloor, LevelLayout, LevelWalls},
+    player::{Player, PlayerMove, PlayerPlugin},
+    progression::ProgressionPlugin,
+    rng::RngPlugin,
+    run::RunPlugin,
+    travel::TravelPlugin,
+    turn::{TurnPlugin, TurnQueue},
+    AppState, LEVEL_LOADED,
+};
+
+// upper bound of frames a single turn may take before `step_turn` gives up
+const MAX_FRAMES_PER_TURN: usize = 256;
+
+/// Runs the game logic on [`MinimalPlugins`], without a window, sprites or LDtk rendering.
+/// Levels are read straight from the LDtk project file, data assets like abilities are still
+/// loaded through the [`AssetPlugin`]. Input is sent as [`PlayerMove`] events or by pressing
+/// actions on the players [`ActionState`]. With a `seed`, every run plays out the same.
+pub struct HeadlessGamePlugin {
+    pub ldtk_path: PathBuf,
+    pub seed: Option<u64>,
+    pub players: usize,
+}
+
+impl Default for HeadlessGamePlugin {
+    fn default() -> Self {
+        Self {
+            ldtk_path: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/game.ldtk"),
+            seed: None,
+            players: 1,
+        }
+    }
+}
+
+impl Plugin for HeadlessGamePlugin {
+    fn build(&self, app: &mut App) {
+        let levels = HeadlessLevels::load(&self.ldtk_path);
+        let authored_levels = AuthoredLevels(Some(levels.0.len()));
+
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(AssetPlugin::default())
+            .add_plugins(StatesPlugin)
+            // no title screen without a window
+            .insert_state(AppState::Loading)
+            // every update advances exactly one fixed timestep, so turns don't depend on the clock
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1.0 / 64.0,
+            )))
+            .insert_resource(levels)
+            .insert_resource(LevelSelection::index(0))
+            .init_resource::<LevelWalls>()
+            .init_resource::<LevelFloor>()
+            .add_plugins(EventsPlugin)
+            .add_plugins(TurnPlugin)
+            .add_plugins(FovPlugin)
+            .add_plugins(ItemPlugin)
+            .add_plugins(ProgressionPlugin)
+            .add_plugins(TravelPlugin)
+            .add_plugins(GridPlugin)
+            .add_plugins(PlayerPlugin {
+                players: self.players,
+            })
+            .add_plugins(SlimePlugin)
+            .add_plugins(EnemyPlugin)
+            .add_plugins(CombatPlugin)
+            .add_plugins(RunPlugin)
+            .add_plugins(RngPlugin { seed: self.seed })
+            .add_plugins(DungeonPlugin)
+            .insert_resource(authored_levels)
+            .add_systems(LEVEL_LOADED, add_headless_input)
+            .add_systems(OnEnter(AppState::Loading), spawn_headless_level);
+    }
+}
+
+#[derive(Resource)]
+pub struct HeadlessLevels(pub Vec<LevelLayout>);
+
+impl HeadlessLevels {
+    pub fn load(path: &Path) -> Self {
+        let json = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("could not read {}: {err}", path.display()));
+        let project: LdtkJson = serde_json::from_str(&json)
+            .unwrap_or_else(|err| panic!("could not parse {}: {err}", path.display()));
+
+        Self(
+            project
+                .levels
+                .iter()
+                .map(LevelLayout::from_ldtk_level)
+                .collect(),
+        )
+    }
+}
+
+/// Root of all entities of the current headless level.
+#[derive(Component)]
+pub struct HeadlessLevel;
+
+// the loading state continues to `InGame` once the abilities are loaded
+fn spawn_headless_level(
+    mut commands: Commands,
+    levels: Res<HeadlessLevels>,
+    level_selection: Res<LevelSelection>,
+    previous_levels: Query<Entity, With<HeadlessLevel>>,
+    mut level_walls: ResMut<LevelWalls>,
+    mut level_floor: ResMut<LevelFloor>,
+) {
+    for entity in previous_levels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let index = match level_selection.into_inner() {
+        LevelSelection::Indices(indices) => indices.level,
+        _ => panic!("level selection should always be Indices in this game"),
+    };
+
+    // levels past the authored ones are generated by the DungeonPlugin
+    let Some(layout) = levels.0.get(index) else {
+        return;
+    };
+
+    *level_walls = layout.level_walls();
+    *level_floor = layout.level_floor();
+
+    let level = spawn_level_layout(&mut commands, layout, format!("Level_{index}"));
+    commands.entity(level).insert(HeadlessLevel);
+}
+
+// stands in for the input manager the input plugin adds to players
+fn add_headless_input(
+    mut commands: Commands,
+    players: Query<Entity, (With<Player>, Without<ActionState<PlayerInputAction>>)>,
+) {
+    for entity in players.iter() {
+        commands
+            .entity(entity)
+            .insert(ActionState::<PlayerInputAction>::default());
+    }
+}
+
+pub trait HeadlessAppExt {
+    /// Runs the app until the current level is loaded and one of the players may act.
+    fn wait_for_player_turn(&mut self);
+
+    /// Moves the players by `direction` and runs the app until every actor has taken its turn.
+    fn step_turn(&mut self, direction: GridCoords);
+}
+
+impl HeadlessAppExt for App {
+    fn wait_for_player_turn(&mut self) {
+        for _ in 0..MAX_FRAMES_PER_TURN {
+            self.update();
+
+            if current_player(self).is_some() {
+                return;
+            }
+        }
+
+        panic!("the player did not get a turn within {MAX_FRAMES_PER_TURN} frames");
+    }
+
+    fn step_turn(&mut self, direction: GridCoords) {
+        self.wait_for_player_turn();
+
+        let start = self.world().resource::<TurnQueue>().now();
+        for _ in 0..MAX_FRAMES_PER_TURN {
+            // with several players, each of them moves once it's their turn
+            if let Some(player) = current_player(self) {
+                self.world_mut()
+                    .send_event(PlayerMove { player, direction });
+            }
+            self.update();
+
+            if self.world().resource::<TurnQueue>().now() > start {
+                return;
+            }
+        }
+
+        panic!("the turn did not end within {MAX_FRAMES_PER_TURN} frames");
+    }
+}
+
+fn current_player(app: &mut App) -> Option<Entity> {
+    let current = app.world().resource::<TurnQueue>().current()?;
+    let mut players = app.world_mut().query_filtered::<(), With<Player>>();
+    players.get(app.world(), current).ok().map(|_| current)
+}
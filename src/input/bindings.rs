@@ -0,0 +1,302 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::save::SaveError;
+
+use super::{InputDevices, MenuAction, PlayerInputAction};
+
+/// Where the player bindings are persisted.
+#[derive(Resource, Debug)]
+pub struct ControlsFile(pub PathBuf);
+
+/// Keyboard keys and gamepad button of every [`PlayerInputAction`], players get their
+/// [`InputMap`] from these. The left stick always moves as well.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Controls {
+    pub bindings: Vec<ActionBinding>,
+}
+
+/// Rebinding replaces `key`, the `alternate_keys` stay as they are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionBinding {
+    pub action: PlayerInputAction,
+    pub key: Option<KeyCode>,
+    #[serde(default)]
+    pub alternate_keys: Vec<KeyCode>,
+    pub gamepad: Option<GamepadButtonType>,
+}
+
+impl ActionBinding {
+    pub fn keys(&self) -> impl Iterator<Item = KeyCode> + '_ {
+        self.key.iter().chain(self.alternate_keys.iter()).copied()
+    }
+}
+
+// not rebindable, the stick has no place in the controls menu
+const STICK: [(PlayerInputAction, GamepadControlDirection); 4] = [
+    (PlayerInputAction::Up, GamepadControlDirection::LEFT_UP),
+    (PlayerInputAction::Down, GamepadControlDirection::LEFT_DOWN),
+    (PlayerInputAction::Left, GamepadControlDirection::LEFT_LEFT),
+    (
+        PlayerInputAction::Right,
+        GamepadControlDirection::LEFT_RIGHT,
+    ),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindingInput {
+    Key(KeyCode),
+    Gamepad(GamepadButtonType),
+}
+
+/// Why an input can't be bound, it already triggers something else while playing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindingConflict {
+    Action(PlayerInputAction),
+    Menu(MenuAction),
+}
+
+impl fmt::Display for BindingConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingConflict::Action(action) => write!(f, "already bound to {action:?}"),
+            BindingConflict::Menu(action) => write!(f, "reserved for {action:?}"),
+        }
+    }
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        use PlayerInputAction::*;
+
+        let binding = |action, key, alternate_keys: &[KeyCode], gamepad| ActionBinding {
+            action,
+            key: Some(key),
+            alternate_keys: alternate_keys.to_vec(),
+            gamepad,
+        };
+
+        Self {
+            bindings: vec![
+                // Movement, WASD and the numpad with vi-keys as alternatives
+                binding(
+                    Up,
+                    KeyCode::KeyW,
+                    &[KeyCode::Numpad8, KeyCode::KeyK],
+                    Some(GamepadButtonType::DPadUp),
+                ),
+                binding(
+                    Down,
+                    KeyCode::KeyS,
+                    &[KeyCode::Numpad2, KeyCode::KeyJ],
+                    Some(GamepadButtonType::DPadDown),
+                ),
+                binding(
+                    Left,
+                    KeyCode::KeyA,
+                    &[KeyCode::Numpad4, KeyCode::KeyH],
+                    Some(GamepadButtonType::DPadLeft),
+                ),
+                binding(
+                    Right,
+                    KeyCode::KeyD,
+                    &[KeyCode::Numpad6, KeyCode::KeyL],
+                    Some(GamepadButtonType::DPadRight),
+                ),
+                // diagonals on the gamepad come from pressing two directions at once
+                binding(UpLeft, KeyCode::Numpad7, &[KeyCode::KeyY], None),
+                binding(UpRight, KeyCode::Numpad9, &[KeyCode::KeyU], None),
+                binding(DownLeft, KeyCode::Numpad1, &[KeyCode::KeyB], None),
+                binding(DownRight, KeyCode::Numpad3, &[KeyCode::KeyN], None),
+                // Abilities
+                binding(Ability1, KeyCode::KeyQ, &[], Some(GamepadButtonType::West)),
+                binding(Ability2, KeyCode::KeyE, &[], Some(GamepadButtonType::North)),
+                binding(
+                    Ability3,
+                    KeyCode::KeyR,
+                    &[],
+                    Some(GamepadButtonType::LeftTrigger),
+                ),
+                binding(
+                    Ability4,
+                    KeyCode::KeyF,
+                    &[],
+                    Some(GamepadButtonType::RightTrigger),
+                ),
+                binding(Tab, KeyCode::Tab, &[], None),
+                binding(Explore, KeyCode::KeyO, &[], Some(GamepadButtonType::East)),
+                // the inventory panel is clicked with the mouse, gamepads can't use it
+                binding(Inventory, KeyCode::KeyI, &[], None),
+            ],
+        }
+    }
+}
+
+impl Controls {
+    /// Reads the bindings from `path`, falling back to the defaults if there are none yet.
+    pub fn load(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match Self::read(path) {
+            Ok(mut controls) => {
+                controls.add_missing_defaults();
+                controls
+            }
+            Err(err) => {
+                error!("{err}, using the default controls");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SaveError> {
+        let ron = fs::read_to_string(path)?;
+        Ok(ron::from_str(&ron)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, ron)?;
+        Ok(())
+    }
+
+    // actions added after the file was written would be unbound otherwise, their default keys
+    // are only taken if nothing else uses them by now
+    fn add_missing_defaults(&mut self) {
+        for mut default in Self::default().bindings {
+            if self.binding(default.action).is_some() {
+                continue;
+            }
+
+            default.key = default.key.filter(|key| self.bound_to_key(*key).is_none());
+            default
+                .alternate_keys
+                .retain(|key| self.bound_to_key(*key).is_none());
+            default.gamepad = default
+                .gamepad
+                .filter(|button| self.bound_to_button(*button).is_none());
+            self.bindings.push(default);
+        }
+    }
+
+    /// Bindings of the inputs a player with `devices` can use.
+    pub fn input_map(&self, devices: InputDevices) -> InputMap<PlayerInputAction> {
+        let keyboard = !matches!(devices, InputDevices::Gamepad(_));
+        let gamepad = devices != InputDevices::KeyboardAndMouse;
+
+        let mut input_map = InputMap::default();
+        for binding in self.bindings.iter() {
+            if keyboard {
+                for key in binding.keys() {
+                    input_map.insert(binding.action, key);
+                }
+            }
+            if let Some(button) = binding.gamepad.filter(|_| gamepad) {
+                input_map.insert(binding.action, button);
+            }
+        }
+        if gamepad {
+            for (action, direction) in STICK {
+                input_map.insert(action, direction);
+            }
+        }
+        if let InputDevices::Gamepad(gamepad) = devices {
+            input_map.set_gamepad(gamepad);
+        }
+
+        input_map
+    }
+
+    pub fn binding(&self, action: PlayerInputAction) -> Option<&ActionBinding> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.action == action)
+    }
+
+    /// Binds `input` to `action`, replacing its previous key or gamepad button. Nothing changes if
+    /// `input` is already in use.
+    pub fn rebind(
+        &mut self,
+        action: PlayerInputAction,
+        input: BindingInput,
+    ) -> Result<(), BindingConflict> {
+        if let Some(menu_action) = reserved_by_menu(input) {
+            return Err(BindingConflict::Menu(menu_action));
+        }
+
+        let bound_to = match input {
+            BindingInput::Key(key) => self.bound_to_key(key),
+            BindingInput::Gamepad(button) => self.bound_to_button(button),
+        };
+        if let Some(other) = bound_to.filter(|other| *other != action) {
+            return Err(BindingConflict::Action(other));
+        }
+
+        let index = match self
+            .bindings
+            .iter()
+            .position(|binding| binding.action == action)
+        {
+            Some(index) => index,
+            None => {
+                self.bindings.push(ActionBinding {
+                    action,
+                    key: None,
+                    alternate_keys: Vec::new(),
+                    gamepad: None,
+                });
+                self.bindings.len() - 1
+            }
+        };
+
+        match input {
+            BindingInput::Key(key) => self.bindings[index].key = Some(key),
+            BindingInput::Gamepad(button) => self.bindings[index].gamepad = Some(button),
+        }
+
+        Ok(())
+    }
+
+    fn bound_to_key(&self, key: KeyCode) -> Option<PlayerInputAction> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.keys().any(|bound| bound == key))
+            .map(|binding| binding.action)
+    }
+
+    fn bound_to_button(&self, button: GamepadButtonType) -> Option<PlayerInputAction> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.gamepad == Some(button))
+            .map(|binding| binding.action)
+    }
+}
+
+// menu actions that also work while playing, the others are only read inside the menus
+fn reserved_by_menu(input: BindingInput) -> Option<MenuAction> {
+    let in_game = |menu_action: &MenuAction| {
+        matches!(
+            menu_action,
+            MenuAction::Pause | MenuAction::QuickSave | MenuAction::QuickLoad
+        )
+    };
+
+    match input {
+        BindingInput::Key(key) => MenuAction::KEYS
+            .iter()
+            .find(|(menu_action, bound)| in_game(menu_action) && *bound == key)
+            .map(|(menu_action, _)| *menu_action),
+        BindingInput::Gamepad(button) => MenuAction::BUTTONS
+            .iter()
+            .find(|(menu_action, bound)| in_game(menu_action) && *bound == button)
+            .map(|(menu_action, _)| *menu_action),
+    }
+}
@@ -0,0 +1,297 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::GridCoords;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub mod bindings;
+
+use bindings::{Controls, ControlsFile};
+
+use crate::{
+    combat::ability::ABILITY_SLOTS,
+    events::{LoadRun, SaveRun},
+    player::{LocalPlayers, Player, PlayerId, PlayerMove},
+    replay::Replay,
+    AppState, LEVEL_LOADED,
+};
+
+/// Reads player and menu input, the player bindings are loaded from and saved to
+/// `controls_path`.
+pub struct InputPlugin {
+    pub controls_path: PathBuf,
+}
+
+impl Default for InputPlugin {
+    fn default() -> Self {
+        Self {
+            controls_path: PathBuf::from("controls.ron"),
+        }
+    }
+}
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Controls::load(&self.controls_path))
+            .insert_resource(ControlsFile(self.controls_path.clone()))
+            .add_plugins(InputManagerPlugin::<PlayerInputAction>::default())
+            .add_plugins(InputManagerPlugin::<MenuAction>::default())
+            .init_resource::<ActionState<MenuAction>>()
+            .insert_resource(MenuAction::default_input_map())
+            .add_systems(LEVEL_LOADED, add_player_input_manager)
+            .add_systems(
+                Update,
+                (
+                    toggle_menu.run_if(in_state(AppState::InGame)),
+                    quick_save_and_load,
+                    apply_controls.run_if(resource_changed::<Controls>),
+                ),
+            )
+            .add_systems(Update, (move_player).run_if(in_state(AppState::InGame)));
+    }
+}
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect, Serialize, Deserialize)]
+pub enum PlayerInputAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    Ability1,
+    Ability2,
+    Ability3,
+    Ability4,
+    Tab,
+    Explore,
+    Inventory,
+}
+
+impl PlayerInputAction {
+    pub const DIRECTIONS: [Self; 8] = [
+        PlayerInputAction::Up,
+        PlayerInputAction::Down,
+        PlayerInputAction::Left,
+        PlayerInputAction::Right,
+        PlayerInputAction::UpLeft,
+        PlayerInputAction::UpRight,
+        PlayerInputAction::DownLeft,
+        PlayerInputAction::DownRight,
+    ];
+
+    pub const ALL: [Self; 15] = [
+        PlayerInputAction::Up,
+        PlayerInputAction::Down,
+        PlayerInputAction::Left,
+        PlayerInputAction::Right,
+        PlayerInputAction::UpLeft,
+        PlayerInputAction::UpRight,
+        PlayerInputAction::DownLeft,
+        PlayerInputAction::DownRight,
+        PlayerInputAction::Ability1,
+        PlayerInputAction::Ability2,
+        PlayerInputAction::Ability3,
+        PlayerInputAction::Ability4,
+        PlayerInputAction::Tab,
+        PlayerInputAction::Explore,
+        PlayerInputAction::Inventory,
+    ];
+
+    pub const ABILITIES: [Self; ABILITY_SLOTS] = [
+        PlayerInputAction::Ability1,
+        PlayerInputAction::Ability2,
+        PlayerInputAction::Ability3,
+        PlayerInputAction::Ability4,
+    ];
+
+    /// The action bar slot the action casts from.
+    pub fn ability_slot(self) -> Option<usize> {
+        match self {
+            PlayerInputAction::Ability1 => Some(0),
+            PlayerInputAction::Ability2 => Some(1),
+            PlayerInputAction::Ability3 => Some(2),
+            PlayerInputAction::Ability4 => Some(3),
+            _ => None,
+        }
+    }
+
+    fn direction(self) -> Option<GridCoords> {
+        match self {
+            PlayerInputAction::Up => Some(GridCoords::new(0, 1)),
+            PlayerInputAction::Down => Some(GridCoords::new(0, -1)),
+            PlayerInputAction::Left => Some(GridCoords::new(-1, 0)),
+            PlayerInputAction::Right => Some(GridCoords::new(1, 0)),
+            PlayerInputAction::UpLeft => Some(GridCoords::new(-1, 1)),
+            PlayerInputAction::UpRight => Some(GridCoords::new(1, 1)),
+            PlayerInputAction::DownLeft => Some(GridCoords::new(-1, -1)),
+            PlayerInputAction::DownRight => Some(GridCoords::new(1, -1)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect)]
+pub enum MenuAction {
+    Pause,
+    Select,
+    Up,
+    Down,
+    QuickSave,
+    QuickLoad,
+}
+
+impl MenuAction {
+    // menu controls are fixed, players can't rebind them
+    const KEYS: [(Self, KeyCode); 7] = [
+        (MenuAction::Pause, KeyCode::Escape),
+        (MenuAction::Pause, KeyCode::Semicolon),
+        (MenuAction::Select, KeyCode::Enter),
+        (MenuAction::Up, KeyCode::ArrowUp),
+        (MenuAction::Down, KeyCode::ArrowDown),
+        (MenuAction::QuickSave, KeyCode::F5),
+        (MenuAction::QuickLoad, KeyCode::F9),
+    ];
+
+    const BUTTONS: [(Self, GamepadButtonType); 4] = [
+        (MenuAction::Pause, GamepadButtonType::Start),
+        (MenuAction::Select, GamepadButtonType::South),
+        (MenuAction::Up, GamepadButtonType::DPadUp),
+        (MenuAction::Down, GamepadButtonType::DPadDown),
+    ];
+
+    fn default_input_map() -> InputMap<Self> {
+        let mut input_map = InputMap::default();
+
+        for (action, key) in Self::KEYS {
+            input_map.insert(action, key);
+        }
+        for (action, button) in Self::BUTTONS {
+            input_map.insert(action, button);
+        }
+
+        input_map
+    }
+}
+
+/// Devices a player's actions are read from. A single player can use all of them, in co-op the
+/// first player gets keyboard and mouse and every other player a gamepad of their own.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputDevices {
+    All,
+    KeyboardAndMouse,
+    Gamepad(Gamepad),
+}
+
+impl InputDevices {
+    pub fn of(id: PlayerId, local_players: &LocalPlayers) -> Self {
+        match id.0 {
+            _ if !local_players.is_coop() => InputDevices::All,
+            0 => InputDevices::KeyboardAndMouse,
+            id => InputDevices::Gamepad(Gamepad::new(id - 1)),
+        }
+    }
+
+    /// Whether the player aims with the mouse cursor.
+    pub fn uses_mouse(self) -> bool {
+        !matches!(self, InputDevices::Gamepad(_))
+    }
+}
+
+#[derive(Bundle)]
+struct PlayerInputBundle {
+    input_manager: InputManagerBundle<PlayerInputAction>,
+    devices: InputDevices,
+}
+
+fn add_player_input_manager(
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerId), With<Player>>,
+    controls: Res<Controls>,
+    local_players: Res<LocalPlayers>,
+) {
+    assert!(
+        !players.is_empty(),
+        "Player should always exist at this stage or the game is broken"
+    );
+
+    for (entity, id) in players.iter() {
+        let devices = InputDevices::of(*id, &local_players);
+        commands.entity(entity).insert(PlayerInputBundle {
+            input_manager: InputManagerBundle::with_map(controls.input_map(devices)),
+            devices,
+        });
+    }
+}
+
+// rebinding applies right away, not only from the next level on
+fn apply_controls(
+    controls: Res<Controls>,
+    mut input_maps: Query<(&InputDevices, &mut InputMap<PlayerInputAction>), With<Player>>,
+) {
+    for (devices, mut input_map) in input_maps.iter_mut() {
+        *input_map = controls.input_map(*devices);
+    }
+}
+
+/// Moves players in their pressed direction, unless a [`Replay`] is running in place of input.
+pub fn move_player(
+    query: Query<(Entity, &ActionState<PlayerInputAction>), With<Player>>,
+    replay: Option<Res<Replay>>,
+    mut move_direction: EventWriter<PlayerMove>,
+) {
+    if replay.is_some() {
+        return;
+    }
+
+    for (player, action_state) in query.iter() {
+        // pressed directions add up, so Up and Right on the stick or d-pad move diagonally and
+        // opposite directions cancel out
+        let direction = PlayerInputAction::DIRECTIONS
+            .into_iter()
+            .filter(|input_direction| action_state.pressed(input_direction))
+            .filter_map(PlayerInputAction::direction)
+            .fold(GridCoords::new(0, 0), |sum, direction| sum + direction);
+
+        move_direction.send(PlayerMove {
+            player,
+            direction: GridCoords::new(direction.x.clamp(-1, 1), direction.y.clamp(-1, 1)),
+        });
+    }
+}
+
+// the menu closes itself, see `crate::ui::menu`
+fn toggle_menu(
+    action_state: Res<ActionState<MenuAction>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if action_state.just_pressed(&MenuAction::Pause) {
+        next_state.set(AppState::Menu);
+    }
+}
+
+fn quick_save_and_load(
+    action_state: Res<ActionState<MenuAction>>,
+    state: Res<State<AppState>>,
+    mut save_ew: EventWriter<SaveRun>,
+    mut load_ew: EventWriter<LoadRun>,
+) {
+    let state = state.get();
+
+    // a level has to be there to save it
+    if matches!(state, AppState::InGame | AppState::Menu)
+        && action_state.just_pressed(&MenuAction::QuickSave)
+    {
+        save_ew.send(SaveRun);
+    }
+
+    // loading also continues a run from the game over screen
+    if matches!(state, AppState::InGame | AppState::GameOver)
+        && action_state.just_pressed(&MenuAction::QuickLoad)
+    {
+        load_ew.send(LoadRun);
+    }
+}
@@ -0,0 +1,486 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{ldtk::TilesetDefinition, prelude::*, utils::grid_coords_to_translation};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{ability::AbilityCooldowns, stats::Stats},
+    events::{PlayerActed, PlayerTurnAction},
+    player::{Player, PlayerAction},
+    turn::{ActionCost, TurnQueue, TurnSet},
+    AppState, Health, GRID_SIZE, ITEM_Z_INDEX,
+};
+
+/// Items lying in levels and the inventories and equipment of players. Stepping onto an item
+/// picks it up, using, equipping or dropping one takes the turn. Inventories and equipment are
+/// carried down the stairs.
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<UseItem>()
+            .add_event::<DropItem>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    (use_item, drop_item).chain().in_set(TurnSet::Player),
+                    pick_up_items.after(TurnSet::Player).before(TurnSet::Enemy),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .register_type::<Item>()
+            .register_type::<Inventory>()
+            .register_type::<Equipment>();
+    }
+}
+
+/// Sprites of items, left out when running headless.
+pub struct ItemVisualsPlugin;
+
+impl Plugin for ItemVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, add_item_sprites.run_if(in_state(AppState::InGame)));
+    }
+}
+
+pub const INVENTORY_CAPACITY: usize = 8;
+
+const POTION_HEALING: i32 = 30;
+const ITEM_SIZE: f32 = 8.0;
+
+/// Something to pick up. Levels place items as LDtk entities named after them, e.g. `Potion`.
+#[derive(
+    Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize,
+)]
+pub enum Item {
+    /// Heals the player.
+    #[default]
+    Potion,
+    /// Makes all abilities ready again.
+    Scroll,
+    /// A weapon for physical hits.
+    Sword,
+    /// A weapon for spells.
+    Staff,
+    Armor,
+    /// A trinket that adds to the maximum health.
+    Amulet,
+}
+
+impl Item {
+    pub const ALL: [Self; 6] = [
+        Item::Potion,
+        Item::Scroll,
+        Item::Sword,
+        Item::Staff,
+        Item::Armor,
+        Item::Amulet,
+    ];
+
+    /// The item an LDtk entity or level layout entity with `identifier` stands for.
+    pub fn from_identifier(identifier: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|item| item.identifier() == identifier)
+    }
+
+    pub fn identifier(self) -> &'static str {
+        match self {
+            Item::Potion => "Potion",
+            Item::Scroll => "Scroll",
+            Item::Sword => "Sword",
+            Item::Staff => "Staff",
+            Item::Armor => "Armor",
+            Item::Amulet => "Amulet",
+        }
+    }
+
+    /// The slot the item is worn in, `None` for items that are used up instead.
+    pub fn equipment_slot(self) -> Option<EquipmentSlot> {
+        match self {
+            Item::Potion | Item::Scroll => None,
+            Item::Sword | Item::Staff => Some(EquipmentSlot::Weapon),
+            Item::Armor => Some(EquipmentSlot::Armor),
+            Item::Amulet => Some(EquipmentSlot::Trinket),
+        }
+    }
+
+    /// What wearing the item adds to the stats of the player.
+    pub fn bonus(self) -> Stats {
+        match self {
+            Item::Potion | Item::Scroll => Stats::default(),
+            Item::Sword => Stats {
+                attack: 10,
+                ..default()
+            },
+            Item::Staff => Stats {
+                spell_power: 10,
+                ..default()
+            },
+            Item::Armor => Stats {
+                defense: 5,
+                ..default()
+            },
+            Item::Amulet => Stats {
+                max_health: 20,
+                ..default()
+            },
+        }
+    }
+
+    /// Shown when the item is inspected in the inventory.
+    pub fn description(self) -> String {
+        match self {
+            Item::Potion => format!("Heals {POTION_HEALING} health."),
+            Item::Scroll => "Makes all abilities ready to cast again.".to_string(),
+            Item::Sword | Item::Staff | Item::Armor | Item::Amulet => {
+                let bonus = self.bonus();
+                let bonuses: Vec<String> = [
+                    (bonus.attack, "attack"),
+                    (bonus.defense, "defense"),
+                    (bonus.spell_power, "spell power"),
+                    (bonus.max_health, "maximum health"),
+                ]
+                .into_iter()
+                .filter(|(value, _)| *value != 0)
+                .map(|(value, stat)| format!("{value:+} {stat}"))
+                .collect();
+                format!("{}, {}.", self.equipment_slot_name(), bonuses.join(", "))
+            }
+        }
+    }
+
+    fn equipment_slot_name(self) -> &'static str {
+        match self.equipment_slot() {
+            Some(EquipmentSlot::Weapon) => "Weapon",
+            Some(EquipmentSlot::Armor) => "Armor",
+            Some(EquipmentSlot::Trinket) => "Trinket",
+            None => "Item",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Item::Potion => Color::srgb(0.9, 0.2, 0.3),
+            Item::Scroll => Color::srgb(0.9, 0.85, 0.6),
+            Item::Sword => Color::srgb(0.75, 0.75, 0.8),
+            Item::Staff => Color::srgb(0.55, 0.3, 0.9),
+            Item::Armor => Color::srgb(0.45, 0.3, 0.2),
+            Item::Amulet => Color::srgb(0.2, 0.8, 0.7),
+        }
+    }
+}
+
+impl LdtkEntity for Item {
+    fn bundle_entity(
+        entity_instance: &EntityInstance,
+        _: &LayerInstance,
+        _: Option<&Handle<Image>>,
+        _: Option<&TilesetDefinition>,
+        _: &AssetServer,
+        _: &mut Assets<TextureAtlasLayout>,
+    ) -> Self {
+        Item::from_identifier(&entity_instance.identifier)
+            .expect("items are only registered under their own identifier")
+    }
+}
+
+/// Items a player carries, up to `capacity` of them.
+#[derive(Component, Clone, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct Inventory {
+    pub capacity: usize,
+    items: Vec<Item>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new(INVENTORY_CAPACITY)
+    }
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn get(&self, slot: usize) -> Option<Item> {
+        self.items.get(slot).copied()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= self.capacity
+    }
+
+    /// Adds `item`, or hands it back if there is no room left.
+    pub fn add(&mut self, item: Item) -> Result<(), Item> {
+        if self.is_full() {
+            return Err(item);
+        }
+
+        self.items.push(item);
+        Ok(())
+    }
+
+    /// Takes the item out of `slot`, the items after it move up a slot.
+    pub fn remove(&mut self, slot: usize) -> Option<Item> {
+        (slot < self.items.len()).then(|| self.items.remove(slot))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum EquipmentSlot {
+    Weapon,
+    Armor,
+    Trinket,
+}
+
+/// Items a player wears, one per [`EquipmentSlot`]. Their bonuses are added to the player's
+/// [`Stats`].
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct Equipment {
+    pub weapon: Option<Item>,
+    pub armor: Option<Item>,
+    pub trinket: Option<Item>,
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipmentSlot) -> Option<Item> {
+        match slot {
+            EquipmentSlot::Weapon => self.weapon,
+            EquipmentSlot::Armor => self.armor,
+            EquipmentSlot::Trinket => self.trinket,
+        }
+    }
+
+    /// Puts `item` on and returns what was worn in its slot before. Items that can't be worn are
+    /// handed back as the error.
+    pub fn equip(&mut self, item: Item) -> Result<Option<Item>, Item> {
+        let slot = match item.equipment_slot() {
+            Some(EquipmentSlot::Weapon) => &mut self.weapon,
+            Some(EquipmentSlot::Armor) => &mut self.armor,
+            Some(EquipmentSlot::Trinket) => &mut self.trinket,
+            None => return Err(item),
+        };
+        Ok(slot.replace(item))
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = Item> {
+        [self.weapon, self.armor, self.trinket]
+            .into_iter()
+            .flatten()
+    }
+
+    /// What all worn items add to the stats of the player.
+    pub fn bonus(&self) -> Stats {
+        self.items()
+            .map(Item::bonus)
+            .fold(Stats::default(), |sum, bonus| sum + bonus)
+    }
+}
+
+/// Asks to use the item in `slot` of the `player`s inventory. Items that can't be used right now
+/// are kept and the turn isn't spent.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct UseItem {
+    pub player: Entity,
+    pub slot: usize,
+}
+
+/// Asks to put the item in `slot` of the `player`s inventory down where they stand.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DropItem {
+    pub player: Entity,
+    pub slot: usize,
+}
+
+/// Spawns `item` lying on `coords`, below `level` if given so it goes away with the level.
+pub fn spawn_item(
+    commands: &mut Commands,
+    item: Item,
+    coords: GridCoords,
+    iid: Option<EntityIid>,
+    level: Option<Entity>,
+) -> Entity {
+    let mut entity_commands = commands.spawn((
+        item,
+        coords,
+        SpatialBundle::from_transform(Transform::from_translation(
+            grid_coords_to_translation(coords, IVec2::splat(GRID_SIZE)).extend(ITEM_Z_INDEX),
+        )),
+        Name::new(item.identifier()),
+    ));
+    if let Some(iid) = iid {
+        entity_commands.insert(iid);
+    }
+
+    let entity = entity_commands.id();
+    if let Some(level) = level {
+        commands.entity(level).add_child(entity);
+    }
+    entity
+}
+
+#[allow(clippy::type_complexity)]
+fn use_item(
+    mut use_item_er: EventReader<UseItem>,
+    mut players: Query<
+        (
+            &mut Inventory,
+            &mut Equipment,
+            &mut Health,
+            &PlayerAction,
+            Option<&mut AbilityCooldowns>,
+        ),
+        With<Player>,
+    >,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut player_acted_ew: EventWriter<PlayerActed>,
+) {
+    for &UseItem { player, slot } in use_item_er.read() {
+        let Ok((mut inventory, mut equipment, mut health, player_action, cooldowns)) =
+            players.get_mut(player)
+        else {
+            continue;
+        };
+
+        if matches!(player_action, PlayerAction::Combat | PlayerAction::Dead)
+            || !turn_queue.is_turn_of(player)
+        {
+            continue;
+        }
+
+        let Some(item) = inventory.get(slot) else {
+            continue;
+        };
+
+        match item {
+            Item::Potion => {
+                if health.current_health >= health.max_health {
+                    info!("Already at full health");
+                    continue;
+                }
+                health.current_health =
+                    (health.current_health + POTION_HEALING).min(health.max_health);
+            }
+            Item::Scroll => {
+                let Some(mut cooldowns) =
+                    cooldowns.filter(|cooldowns| cooldowns.0.iter().any(|turns| *turns > 0))
+                else {
+                    info!("All abilities are ready already");
+                    continue;
+                };
+                *cooldowns = AbilityCooldowns::default();
+            }
+            Item::Sword | Item::Staff | Item::Armor | Item::Amulet => (),
+        }
+
+        inventory.remove(slot);
+        match equipment.equip(item) {
+            Ok(previous) => {
+                info!("Equipped a {}", item.identifier());
+                // there is room, the equipped item just left the inventory
+                if let Some(previous) = previous {
+                    let _ = inventory.add(previous);
+                }
+            }
+            Err(_) => info!("Used a {}", item.identifier()),
+        }
+        turn_queue.end_turn(player, ActionCost::UseItem);
+        player_acted_ew.send(PlayerActed {
+            player,
+            action: PlayerTurnAction::UseItem { slot },
+        });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn drop_item(
+    mut commands: Commands,
+    mut drop_item_er: EventReader<DropItem>,
+    mut players: Query<
+        (
+            &mut Inventory,
+            &GridCoords,
+            &PlayerAction,
+            Option<&EntityIid>,
+            Option<&Parent>,
+        ),
+        With<Player>,
+    >,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut player_acted_ew: EventWriter<PlayerActed>,
+) {
+    for &DropItem { player, slot } in drop_item_er.read() {
+        let Ok((mut inventory, coords, player_action, iid, parent)) = players.get_mut(player)
+        else {
+            continue;
+        };
+
+        if matches!(player_action, PlayerAction::Combat | PlayerAction::Dead)
+            || !turn_queue.is_turn_of(player)
+        {
+            continue;
+        }
+
+        let Some(item) = inventory.remove(slot) else {
+            continue;
+        };
+
+        // a player drops at most one item per turn, so the time of the turn tells drops apart
+        let iid =
+            iid.map(|iid| EntityIid::new(format!("{}-dropped-{}", iid.as_str(), turn_queue.now())));
+        spawn_item(
+            &mut commands,
+            item,
+            *coords,
+            iid,
+            parent.map(|parent| parent.get()),
+        );
+
+        turn_queue.end_turn(player, ActionCost::DropItem);
+        player_acted_ew.send(PlayerActed {
+            player,
+            action: PlayerTurnAction::DropItem { slot },
+        });
+    }
+}
+
+// a dropped item stays where it is until the player steps off and back on its tile
+fn pick_up_items(
+    mut commands: Commands,
+    mut players: Query<(&GridCoords, &mut Inventory), (With<Player>, Changed<GridCoords>)>,
+    items: Query<(Entity, &GridCoords, &Item), Without<Player>>,
+) {
+    for (player_pos, mut inventory) in players.iter_mut() {
+        for (entity, _, item) in items.iter().filter(|(_, coords, _)| *coords == player_pos) {
+            match inventory.add(*item) {
+                Ok(()) => {
+                    info!("Picked up a {}", item.identifier());
+                    commands.entity(entity).despawn_recursive();
+                }
+                Err(item) => {
+                    info!("Inventory is full, left the {} behind", item.identifier());
+                }
+            }
+        }
+    }
+}
+
+fn add_item_sprites(mut commands: Commands, items: Query<(Entity, &Item), Added<Item>>) {
+    for (entity, item) in items.iter() {
+        commands.entity(entity).insert((
+            Sprite {
+                color: item.color(),
+                custom_size: Some(Vec2::splat(ITEM_SIZE)),
+                ..default()
+            },
+            Handle::<Image>::default(),
+        ));
+    }
+}
@@ -1,9 +1,20 @@
 use bevy::prelude::*;
-use bevy_ecs_ldtk::prelude::*;
+use bevy_ecs_ldtk::{
+    ldtk::{ldtk_fields::LdtkFields, Level, TilesetDefinition},
+    prelude::*,
+};
 
 use std::collections::HashSet;
 
-use crate::{enemy::slime::Slime, player::Player};
+use crate::{
+    dungeon::AuthoredLevels,
+    enemy::{slime::Slime, PatrolRoute},
+    events::RestartRun,
+    item::Item,
+    player::Player,
+    save::PendingSave,
+    GRID_SIZE,
+};
 
 pub struct LdtkAssetPlugin;
 
@@ -13,13 +24,29 @@ impl Plugin for LdtkAssetPlugin {
             .register_ldtk_entity::<StairsBundle>("Stairs")
             .register_ldtk_entity::<SlimeBundle>("Slime")
             .insert_resource(LevelSelection::index(0))
-            .register_ldtk_int_cell::<WallBundle>(1)
+            .register_ldtk_int_cell::<WallBundle>(WALL_INT_CELL)
             .init_resource::<LevelWalls>()
-            .register_ldtk_int_cell::<FloorBundle>(2)
-            .init_resource::<LevelFloor>();
+            .register_ldtk_int_cell::<FloorBundle>(FLOOR_INT_CELL)
+            .init_resource::<LevelFloor>()
+            .add_systems(Update, (cache_wall_locations, count_authored_levels))
+            .add_systems(
+                Update,
+                respawn_world
+                    .run_if(on_event::<RestartRun>().or_else(resource_added::<PendingSave>)),
+            );
+
+        // every kind of item is an entity of its own in the editor
+        for item in Item::ALL {
+            app.register_ldtk_entity::<ItemBundle>(item.identifier());
+        }
     }
 }
 
+pub const WALL_INT_CELL: i32 = 1;
+pub const FLOOR_INT_CELL: i32 = 2;
+
+const PATROL_FIELD: &str = "Patrol";
+
 #[derive(Default, Component)]
 pub struct Stair;
 
@@ -53,6 +80,14 @@ struct StairsBundle {
     grid_coords: GridCoords,
 }
 
+#[derive(Default, Bundle, LdtkEntity)]
+struct ItemBundle {
+    #[ldtk_entity]
+    item: Item,
+    #[grid_coords]
+    grid_coords: GridCoords,
+}
+
 #[derive(Default, Bundle, LdtkEntity)]
 struct SlimeBundle {
     slime: Slime,
@@ -60,6 +95,33 @@ struct SlimeBundle {
     sprite_sheet_bundle: LdtkSpriteSheetBundle,
     #[grid_coords]
     grid_coords: GridCoords,
+    #[ldtk_entity]
+    patrol_route: PatrolRoute,
+}
+
+impl LdtkEntity for PatrolRoute {
+    fn bundle_entity(
+        entity_instance: &EntityInstance,
+        layer_instance: &LayerInstance,
+        _: Option<&Handle<Image>>,
+        _: Option<&TilesetDefinition>,
+        _: &AssetServer,
+        _: &mut Assets<TextureAtlasLayout>,
+    ) -> Self {
+        PatrolRoute::new(patrol_waypoints(entity_instance, layer_instance.c_hei))
+    }
+}
+
+/// Reads the `Patrol` point array of an entity, entities without one don't patrol.
+fn patrol_waypoints(entity_instance: &EntityInstance, layer_height: i32) -> Vec<GridCoords> {
+    entity_instance
+        .iter_points_field(PATROL_FIELD)
+        .map(|points| {
+            points
+                .map(|point| GridCoords::new(point.x, layer_height - 1 - point.y))
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 #[derive(Default, Bundle, LdtkIntCell)]
@@ -94,6 +156,15 @@ impl LevelWalls {
             || grid_coords.y >= self.level_height
             || self.wall_locations.contains(grid_coords)
     }
+
+    /// Whether a step by `offset` from `from` squeezes diagonally past a wall corner. Diagonal
+    /// steps are only allowed if both tiles they pass between are free of walls.
+    pub fn cuts_corner(&self, from: &GridCoords, offset: GridCoords) -> bool {
+        offset.x != 0
+            && offset.y != 0
+            && (self.in_wall(&GridCoords::new(from.x + offset.x, from.y))
+                || self.in_wall(&GridCoords::new(from.x, from.y + offset.y)))
+    }
 }
 
 impl LevelFloor {
@@ -105,3 +176,134 @@ impl LevelFloor {
             || self.floor_locations.contains(grid_coords)
     }
 }
+
+/// Gameplay relevant content of a level, for spawning levels without the LDtk plugin.
+#[derive(Default, Debug, Clone)]
+pub struct LevelLayout {
+    pub width: i32,
+    pub height: i32,
+    pub walls: HashSet<GridCoords>,
+    pub floor: HashSet<GridCoords>,
+    pub entities: Vec<LayoutEntity>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutEntity {
+    pub identifier: String,
+    pub iid: String,
+    pub coords: GridCoords,
+    pub patrol_route: Vec<GridCoords>,
+}
+
+impl LevelLayout {
+    pub fn from_ldtk_level(level: &Level) -> Self {
+        let mut layout = LevelLayout {
+            width: level.px_wid / GRID_SIZE,
+            height: level.px_hei / GRID_SIZE,
+            ..default()
+        };
+
+        for layer in level.layer_instances.iter().flatten() {
+            // LDtk counts rows from the top, GridCoords from the bottom
+            for (index, value) in layer.int_grid_csv.iter().enumerate() {
+                let index = index as i32;
+                let coords =
+                    GridCoords::new(index % layer.c_wid, layer.c_hei - 1 - index / layer.c_wid);
+                match *value {
+                    WALL_INT_CELL => {
+                        layout.walls.insert(coords);
+                    }
+                    FLOOR_INT_CELL => {
+                        layout.floor.insert(coords);
+                    }
+                    _ => (),
+                }
+            }
+
+            for entity in layer.entity_instances.iter() {
+                layout.entities.push(LayoutEntity {
+                    identifier: entity.identifier.clone(),
+                    iid: entity.iid.clone(),
+                    coords: GridCoords::new(entity.grid.x, layer.c_hei - 1 - entity.grid.y),
+                    patrol_route: patrol_waypoints(entity, layer.c_hei),
+                });
+            }
+        }
+
+        layout
+    }
+
+    pub fn level_walls(&self) -> LevelWalls {
+        LevelWalls {
+            wall_locations: self.walls.clone(),
+            level_width: self.width,
+            level_height: self.height,
+        }
+    }
+
+    pub fn level_floor(&self) -> LevelFloor {
+        LevelFloor {
+            floor_locations: self.floor.clone(),
+            level_width: self.width,
+            level_height: self.height,
+        }
+    }
+}
+
+fn cache_wall_locations(
+    mut level_walls: ResMut<LevelWalls>,
+    mut level_floor: ResMut<LevelFloor>,
+    mut level_events: EventReader<LevelEvent>,
+    walls: Query<&GridCoords, With<Wall>>,
+    floor: Query<&GridCoords, With<Floor>>,
+    ldtk_project_entities: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    for level_event in level_events.read() {
+        if let LevelEvent::Spawned(level_iid) = level_event {
+            let ldtk_project = ldtk_project_assets
+                .get(ldtk_project_entities.single())
+                .expect("LdtkProject should be loaded when level is spawned");
+            let level = ldtk_project
+                .get_raw_level_by_iid(level_iid.get())
+                .expect("spawned level should exist in project");
+
+            let wall_locations = walls.iter().copied().collect();
+
+            let new_level_walls = LevelWalls {
+                wall_locations,
+                level_width: level.px_wid / GRID_SIZE,
+                level_height: level.px_hei / GRID_SIZE,
+            };
+
+            *level_walls = new_level_walls;
+
+            *level_floor = LevelFloor {
+                floor_locations: floor.iter().copied().collect(),
+                level_width: level.px_wid / GRID_SIZE,
+                level_height: level.px_hei / GRID_SIZE,
+            };
+        }
+    }
+}
+
+// respawning the whole world also clears out a level that is still selected
+fn respawn_world(mut commands: Commands, ldtk_worlds: Query<Entity, With<Handle<LdtkProject>>>) {
+    for entity in ldtk_worlds.iter() {
+        commands.entity(entity).insert(Respawn);
+    }
+}
+
+fn count_authored_levels(
+    mut authored_levels: ResMut<AuthoredLevels>,
+    ldtk_project_entities: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    let Ok(ldtk_project) = ldtk_project_entities.get_single() else {
+        return;
+    };
+
+    if let Some(ldtk_project) = ldtk_project_assets.get(ldtk_project) {
+        authored_levels.set_if_neq(AuthoredLevels(Some(ldtk_project.json_data().levels.len())));
+    }
+}
@@ -1,16 +1,28 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use camera::MainCamera;
+use serde::{Deserialize, Serialize};
 use ui::game_cursor::GameCursor;
 pub mod camera;
 
 pub mod combat;
+pub mod dungeon;
 pub mod enemy;
 pub mod events;
+pub mod fov;
 pub mod grid;
+pub mod headless;
 pub mod input;
+pub mod item;
 pub mod ldtk;
 pub mod player;
+pub mod progression;
+pub mod replay;
+pub mod rng;
+pub mod run;
+pub mod save;
+pub mod travel;
+pub mod turn;
 pub mod ui;
 
 pub const GRID_SIZE: i32 = 16;
@@ -19,9 +31,15 @@ pub const GRID_SIZE: i32 = 16;
 pub const ACTION_DELAY: f32 = 0.2;
 pub const ACTIVE_TIME: f32 = 0.5;
 
+// zoom of the camera while it doesn't have to frame several players
+pub const CAMERA_SCALE: f32 = 0.2;
+
 // z-indices
 pub const CURSOR_Z_INDEX: f32 = 100.0;
 pub const ABILITY_Z_INDEX: f32 = 20.0;
+pub const PATH_PREVIEW_Z_INDEX: f32 = 9.0;
+// dropped items, level entities sit at the same height
+pub const ITEM_Z_INDEX: f32 = 2.0;
 
 // helper macros
 
@@ -77,12 +95,18 @@ macro_rules! get_single_mut_or_panic {
     };
 }
 
-#[derive(Component, Reflect)]
+#[derive(Component, Clone, Debug, Reflect, Serialize, Deserialize)]
 pub struct Health {
     pub max_health: i32,
     pub current_health: i32,
 }
 
+impl Health {
+    pub fn fraction(&self) -> f32 {
+        self.current_health as f32 / self.max_health as f32
+    }
+}
+
 impl Default for Health {
     fn default() -> Self {
         Self {
@@ -127,6 +151,17 @@ impl Iterator for IndeciesIter {
     }
 }
 
+impl IndeciesIter {
+    /// True right after the last index of a cycle has been returned.
+    pub fn cycle_finished(&self) -> bool {
+        self.nth >= self.indecies.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.nth = 0;
+    }
+}
+
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum GameplaySet {
     InputSet,
@@ -134,15 +169,32 @@ pub(crate) enum GameplaySet {
 
 #[derive(States, Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum AppState {
+    /// Title screen, the game starts here.
     #[default]
+    MainMenu,
     Loading,
     InGame,
     Menu,
+    GameOver,
 }
 
+/// Runs once a level has been loaded. Unlike `OnEnter(AppState::InGame)` it doesn't run again
+/// when the game is resumed from the pause menu, so patches don't reset what happened so far.
+pub const LEVEL_LOADED: OnTransition<AppState> = OnTransition {
+    exited: AppState::Loading,
+    entered: AppState::InGame,
+};
+
+/// Runs when a level is left for loading another one, by the stairs or by loading a save. What the
+/// players carry to the next floor is stashed here, unless a save is loaded.
+pub const STAIRS_TAKEN: OnTransition<AppState> = OnTransition {
+    exited: AppState::InGame,
+    entered: AppState::Loading,
+};
+
 pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     let mut camera = Camera2dBundle::default();
-    camera.projection.scale = 0.2;
+    camera.projection.scale = CAMERA_SCALE;
     camera.projection.viewport_origin = Vec2::ZERO;
     commands.spawn((camera, MainCamera));
 
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
@@ -5,14 +7,28 @@ use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 use game::{
     camera::CameraPlugin,
-    combat::CombatPlugin,
-    enemy::{health_bar::HealthBarPlugin, slime::SlimePlugin, EnemyPlugin},
+    combat::{CombatPlugin, CombatVisualsPlugin},
+    dungeon::{DungeonPlugin, DungeonVisualsPlugin},
+    enemy::{
+        health_bar::HealthBarPlugin,
+        slime::{SlimeAnimationPlugin, SlimePlugin},
+        EnemyPlugin,
+    },
     events::EventsPlugin,
-    grid::GridPlugin,
+    fov::{FogOfWarPlugin, FovPlugin},
+    grid::{GridOverlayPlugin, GridPlugin},
     input::InputPlugin,
+    item::{ItemPlugin, ItemVisualsPlugin},
     ldtk::LdtkAssetPlugin,
-    player::PlayerPlugin,
+    player::{PlayerAnimationPlugin, PlayerPlugin, MAX_LOCAL_PLAYERS},
+    progression::ProgressionPlugin,
+    replay::ReplayPlugin,
+    rng::RngPlugin,
+    run::RunPlugin,
+    save::SavePlugin,
     setup,
+    travel::{ClickToMovePlugin, TravelPlugin},
+    turn::TurnPlugin,
     ui::UiPlugin,
     AppState,
 };
@@ -24,16 +40,41 @@ fn main() {
         .init_state::<AppState>()
         .add_plugins(LdtkPlugin)
         .add_plugins(LdtkAssetPlugin)
+        .add_plugins(DungeonPlugin)
+        .add_plugins(DungeonVisualsPlugin)
         .add_plugins(GridPlugin)
+        .add_plugins(GridOverlayPlugin)
         .add_plugins(CameraPlugin {
             state: AppState::InGame,
         })
-        .add_plugins(InputPlugin)
-        .add_plugins(PlayerPlugin)
+        .add_plugins(InputPlugin::default())
+        .add_plugins(PlayerPlugin {
+            players: players_from_args(),
+        })
+        .add_plugins(PlayerAnimationPlugin)
         .add_plugins(SlimePlugin)
+        .add_plugins(SlimeAnimationPlugin)
         .add_plugins(EnemyPlugin)
         .add_plugins(CombatPlugin)
+        .add_plugins(CombatVisualsPlugin)
         .add_plugins(EventsPlugin)
+        .add_plugins(TurnPlugin)
+        .add_plugins(FovPlugin)
+        .add_plugins(FogOfWarPlugin)
+        .add_plugins(ItemPlugin)
+        .add_plugins(ProgressionPlugin)
+        .add_plugins(ItemVisualsPlugin)
+        .add_plugins(TravelPlugin)
+        .add_plugins(ClickToMovePlugin)
+        .add_plugins(RunPlugin)
+        .add_plugins(RngPlugin {
+            seed: seed_from_args(),
+        })
+        .add_plugins(ReplayPlugin {
+            replay: arg_value("--replay").map(PathBuf::from),
+            ..default()
+        })
+        .add_plugins(SavePlugin::default())
         .add_plugins(UiPlugin)
         .add_plugins(HealthBarPlugin)
         .add_systems(Startup, setup);
@@ -48,6 +89,39 @@ fn main() {
     app.run();
 }
 
+/// Value passed after `name` on the command line, e.g. `--replay replay.ron`.
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+/// Seed passed as `--seed <seed>`, replays the run the game over screen showed it for.
+fn seed_from_args() -> Option<u64> {
+    let seed = arg_value("--seed")?;
+    match seed.parse() {
+        Ok(seed) => Some(seed),
+        Err(err) => {
+            warn!("Ignoring invalid seed {seed}: {err}");
+            None
+        }
+    }
+}
+
+/// Local co-op with `--players <count>`, every player past the first needs a gamepad.
+fn players_from_args() -> usize {
+    let Some(players) = arg_value("--players") else {
+        return 1;
+    };
+    match players.parse() {
+        Ok(players) if (1..=MAX_LOCAL_PLAYERS).contains(&players) => players,
+        _ => {
+            warn!(
+                "Ignoring invalid number of players {players}, up to {MAX_LOCAL_PLAYERS} can play"
+            );
+            1
+        }
+    }
+}
+
 fn debug_plugins(mut commands: Commands) {
     commands.spawn((
         Name::new("perfui"),
@@ -1,43 +1,164 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
-use bevy_ecs_ldtk::GridCoords;
+use bevy_ecs_ldtk::{EntityIid, GridCoords};
 
 use crate::{
-    events::TurnOver, get_single_mut, grid::Collider, ldtk::LevelWalls, ActionTimer,
-    AnimationTimer, AppState, Health, IdleAnimationTimer, IndeciesIter, ACTION_DELAY,
+    combat::{
+        stats::{BaseStats, Stats},
+        status::{StatusEffects, StatusKind},
+    },
+    enemy::Enemy,
+    events::{PlayerActed, PlayerTurnAction},
+    grid::Collider,
+    item::{Equipment, Inventory},
+    ldtk::{LevelWalls, Stair},
+    progression::Experience,
+    save::PendingSave,
+    turn::{ActionCost, Actor, TurnQueue, TurnSet},
+    ActionTimer, AnimationTimer, AppState, Health, IdleAnimationTimer, IndeciesIter, ACTION_DELAY,
+    GRID_SIZE, LEVEL_LOADED, STAIRS_TAKEN,
 };
 
-pub struct PlayerPlugin;
+/// Players and their turns. Levels place a single player, with more than one local `players`
+/// the others join next to it. What players carry is stashed when they take the stairs and put
+/// back on them on the next level.
+pub struct PlayerPlugin {
+    pub players: usize,
+}
+
+impl Default for PlayerPlugin {
+    fn default() -> Self {
+        Self { players: 1 }
+    }
+}
 
 impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LocalPlayers(self.players.clamp(1, MAX_LOCAL_PLAYERS)))
+            .init_resource::<StashedPlayers>()
+            .add_event::<PlayerMove>()
+            .add_systems(OnExit(AppState::Loading), spawn_local_players)
+            // a loaded save brings along what the players carried
+            .add_systems(
+                STAIRS_TAKEN,
+                stash_players.run_if(not(resource_exists::<PendingSave>)),
+            )
+            .add_systems(LEVEL_LOADED, (patch_players, unstash_players))
+            .add_systems(
+                FixedUpdate,
+                update_player_position
+                    .in_set(TurnSet::Player)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (kill_player, finish_player_death)
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .register_type::<Direction>()
+            .register_type::<PlayerId>()
+            .register_type::<PrimaryPlayer>()
+            .register_type::<PlayerAction>()
+            .register_type::<Health>();
+    }
+}
+
+/// Sprites and animations of the player, left out when running headless.
+pub struct PlayerAnimationPlugin;
+
+impl Plugin for PlayerAnimationPlugin {
     fn build(&self, app: &mut App) {
         app.add_loading_state(
             LoadingState::new(AppState::Loading)
                 .continue_to_state(AppState::InGame)
                 .load_collection::<PlayerAnimation>(),
         )
-        .add_systems(OnEnter(AppState::InGame), patch_players)
+        .add_systems(LEVEL_LOADED, patch_player_sprites)
         .add_systems(
             FixedUpdate,
             (
                 update_player_walking_animation,
                 update_player_idle_animation,
                 update_idle_player_atlas,
-                update_player_position,
+                update_player_death_animation,
             )
                 .run_if(in_state(AppState::InGame)),
-        )
-        .register_type::<Direction>()
-        .register_type::<PlayerAction>()
-        .register_type::<Health>();
+        );
     }
 }
 
+// the player always gets the first turn on a new level
+const PLAYER_INITIATIVE: i32 = 10;
+pub const PLAYER_STATS: Stats = Stats {
+    attack: 0,
+    defense: 0,
+    spell_power: 0,
+    max_health: 100,
+};
+// time the death animation gets before the game over screen shows up
+const DEATH_DELAY: f32 = 1.5;
+
+pub const MAX_LOCAL_PLAYERS: usize = 4;
+
+// tiles around the first player the others join on, in order of preference
+const JOIN_OFFSETS: [GridCoords; 8] = [
+    GridCoords { x: 1, y: 0 },
+    GridCoords { x: -1, y: 0 },
+    GridCoords { x: 0, y: -1 },
+    GridCoords { x: 0, y: 1 },
+    GridCoords { x: 1, y: -1 },
+    GridCoords { x: -1, y: -1 },
+    GridCoords { x: 1, y: 1 },
+    GridCoords { x: -1, y: 1 },
+];
+
 #[derive(Default, Component, Reflect)]
 pub struct Player;
 
-#[derive(Event, Default)]
-pub struct PlayerMove(pub GridCoords);
+/// Number of players playing on this machine.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalPlayers(pub usize);
+
+impl LocalPlayers {
+    pub fn is_coop(&self) -> bool {
+        self.0 > 1
+    }
+}
+
+/// Players are numbered in the order they joined, the first one is the one the level placed.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub struct PlayerId(pub usize);
+
+impl PlayerId {
+    pub const FIRST: Self = PlayerId(0);
+}
+
+/// The player with [`PlayerId::FIRST`], who keeps keyboard and mouse in co-op. The cursor and the
+/// HUD belong to this player.
+#[derive(Component, Default, Reflect)]
+pub struct PrimaryPlayer;
+
+/// What a player takes down the stairs to the next level.
+#[derive(Debug, Default)]
+struct StashedPlayer {
+    inventory: Inventory,
+    equipment: Equipment,
+    experience: Experience,
+}
+
+/// The players on their way down to the next level, the level spawns new player entities.
+#[derive(Resource, Default, Debug)]
+struct StashedPlayers(HashMap<PlayerId, StashedPlayer>);
+
+/// Moves `player` by `direction` once it's their turn, `(0, 0)` lets them stand still.
+#[derive(Event)]
+pub struct PlayerMove {
+    pub player: Entity,
+    pub direction: GridCoords,
+}
 
 #[derive(Component, Default, PartialEq, Debug, Reflect)]
 enum Direction {
@@ -46,6 +167,10 @@ enum Direction {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl TryFrom<GridCoords> for Direction {
@@ -57,6 +182,10 @@ impl TryFrom<GridCoords> for Direction {
             GridCoords { x: 0, y: -1 } => Ok(Direction::Down),
             GridCoords { x: -1, y: 0 } => Ok(Direction::Left),
             GridCoords { x: 1, y: 0 } => Ok(Direction::Right),
+            GridCoords { x: -1, y: 1 } => Ok(Direction::UpLeft),
+            GridCoords { x: 1, y: 1 } => Ok(Direction::UpRight),
+            GridCoords { x: -1, y: -1 } => Ok(Direction::DownLeft),
+            GridCoords { x: 1, y: -1 } => Ok(Direction::DownRight),
             _ => Err(()),
         }
     }
@@ -68,18 +197,59 @@ pub enum PlayerAction {
     Idle,
     Walking,
     Combat,
+    Dead,
 }
 
+#[derive(Component, Deref, DerefMut)]
+pub struct DeathTimer(Timer);
+
 #[derive(Component)]
 struct PlayerAnimationIndecies {
     idle_down: IndeciesIter,
     idle_left: IndeciesIter,
     idle_right: IndeciesIter,
     idle_up: IndeciesIter,
+    idle_up_left: IndeciesIter,
+    idle_up_right: IndeciesIter,
+    idle_down_left: IndeciesIter,
+    idle_down_right: IndeciesIter,
     up: IndeciesIter,
     left: IndeciesIter,
     right: IndeciesIter,
     down: IndeciesIter,
+    up_left: IndeciesIter,
+    up_right: IndeciesIter,
+    down_left: IndeciesIter,
+    down_right: IndeciesIter,
+    dead: IndeciesIter,
+}
+
+impl PlayerAnimationIndecies {
+    fn idle(&mut self, direction: &Direction) -> &mut IndeciesIter {
+        match direction {
+            Direction::Up => &mut self.idle_up,
+            Direction::Down => &mut self.idle_down,
+            Direction::Left => &mut self.idle_left,
+            Direction::Right => &mut self.idle_right,
+            Direction::UpLeft => &mut self.idle_up_left,
+            Direction::UpRight => &mut self.idle_up_right,
+            Direction::DownLeft => &mut self.idle_down_left,
+            Direction::DownRight => &mut self.idle_down_right,
+        }
+    }
+
+    fn walking(&mut self, direction: &Direction) -> &mut IndeciesIter {
+        match direction {
+            Direction::Up => &mut self.up,
+            Direction::Down => &mut self.down,
+            Direction::Left => &mut self.left,
+            Direction::Right => &mut self.right,
+            Direction::UpLeft => &mut self.up_left,
+            Direction::UpRight => &mut self.up_right,
+            Direction::DownLeft => &mut self.down_left,
+            Direction::DownRight => &mut self.down_right,
+        }
+    }
 }
 
 #[derive(AssetCollection, Resource)]
@@ -95,44 +265,147 @@ struct PlayerAnimation {
         offset_y = 8
     ))]
     layout: Handle<TextureAtlasLayout>,
-    #[asset(path = "puny_characters/human_worker_red.png")]
-    texture: Handle<Image>,
+    // one per local player, all sheets share the same layout
+    #[asset(
+        paths(
+            "puny_characters/human_worker_red.png",
+            "puny_characters/human_worker_cyan.png",
+            "puny_characters/soldier_yellow.png",
+            "puny_characters/warrior_blue.png"
+        ),
+        collection(typed)
+    )]
+    textures: Vec<Handle<Image>>,
+}
+
+// levels only place one player, the others join on free tiles around it
+#[allow(clippy::type_complexity)]
+fn spawn_local_players(
+    mut commands: Commands,
+    local_players: Res<LocalPlayers>,
+    players: Query<
+        (
+            Entity,
+            &GridCoords,
+            &Transform,
+            Option<&Parent>,
+            Option<&EntityIid>,
+        ),
+        (With<Player>, Without<PlayerId>),
+    >,
+    taken: Query<&GridCoords, Or<(With<Enemy>, With<Stair>)>>,
+    level_walls: Res<LevelWalls>,
+) {
+    for (entity, coords, transform, parent, iid) in players.iter() {
+        commands
+            .entity(entity)
+            .insert((PlayerId::FIRST, PrimaryPlayer));
+
+        let mut free_offsets = JOIN_OFFSETS.into_iter().filter(|offset| {
+            let joined_coords = *coords + *offset;
+            !level_walls.in_wall(&joined_coords)
+                && !taken
+                    .iter()
+                    .any(|taken_coords| *taken_coords == joined_coords)
+        });
+
+        for id in 1..local_players.0 {
+            let Some(offset) = free_offsets.next() else {
+                warn!("No room next to the first player for player {}", id + 1);
+                break;
+            };
+
+            let translation = transform.translation
+                + Vec3::new(
+                    (offset.x * GRID_SIZE) as f32,
+                    (offset.y * GRID_SIZE) as f32,
+                    0.0,
+                );
+            let mut joined = commands.spawn((
+                Player,
+                PlayerId(id),
+                *coords + offset,
+                SpriteBundle {
+                    transform: Transform::from_translation(translation),
+                    ..default()
+                },
+                TextureAtlas::default(),
+                Name::new(format!("Player {}", id + 1)),
+            ));
+            // saves find the joined players by the iid of the first one
+            if let Some(iid) = iid {
+                joined.insert(EntityIid::new(format!("{}-{id}", iid.as_str())));
+            }
+
+            let joined = joined.id();
+            if let Some(parent) = parent {
+                commands.entity(parent.get()).add_child(joined);
+            }
+        }
+    }
+}
+
+fn patch_players(mut commands: Commands, player_query: Query<Entity, With<Player>>) {
+    for entity in &player_query {
+        commands.entity(entity).insert((
+            ActionTimer(Timer::from_seconds(ACTION_DELAY, TimerMode::Repeating)),
+            PlayerAction::default(),
+            Direction::default(),
+            Health::default(),
+            BaseStats(PLAYER_STATS),
+            PLAYER_STATS,
+            StatusEffects::default(),
+            Actor {
+                initiative: PLAYER_INITIATIVE,
+                ..default()
+            },
+        ));
+    }
 }
 
-fn patch_players(
+fn patch_player_sprites(
     mut commands: Commands,
     asset: Res<PlayerAnimation>,
-    mut player_query: Query<(Entity, &mut TextureAtlas, &mut Handle<Image>), With<Player>>,
+    mut player_query: Query<
+        (Entity, &PlayerId, &mut TextureAtlas, &mut Handle<Image>),
+        With<Player>,
+    >,
 ) {
-    for (entity, mut atlas, mut texture) in &mut player_query {
+    for (entity, id, mut atlas, mut texture) in &mut player_query {
         let player_animation_indices = patch_player_animation();
         atlas.layout = asset.layout.clone();
-        *texture = asset.texture.clone();
+        *texture = asset.textures[id.0 % asset.textures.len()].clone();
         commands.entity(entity).insert((
             AnimationTimer(Timer::from_seconds(
                 ACTION_DELAY / 2.0,
                 TimerMode::Repeating,
             )),
-            ActionTimer(Timer::from_seconds(ACTION_DELAY, TimerMode::Repeating)),
             IdleAnimationTimer(Timer::from_seconds(1.0, TimerMode::Repeating)),
             player_animation_indices,
-            PlayerAction::default(),
-            Direction::default(),
-            Health::default(),
         ));
     }
 }
 
+// every row of the sheet is one facing, going counterclockwise from down
 fn patch_player_animation() -> PlayerAnimationIndecies {
     PlayerAnimationIndecies {
         idle_down: vec![0, 1].into(),
         idle_left: vec![144, 145].into(),
         idle_right: vec![48, 49].into(),
         idle_up: vec![96, 97].into(),
+        idle_up_left: vec![120, 121].into(),
+        idle_up_right: vec![72, 73].into(),
+        idle_down_left: vec![168, 169].into(),
+        idle_down_right: vec![24, 25].into(),
         up: vec![98, 99, 100, 99].into(),
         left: vec![146, 147, 148, 147].into(),
         right: vec![50, 51, 52, 51].into(),
         down: vec![2, 3, 4, 3].into(),
+        up_left: vec![122, 123, 124, 123].into(),
+        up_right: vec![74, 75, 76, 75].into(),
+        down_left: vec![170, 171, 172, 171].into(),
+        down_right: vec![26, 27, 28, 27].into(),
+        dead: vec![20, 21, 22, 23].into(),
     }
 }
 
@@ -153,20 +426,10 @@ fn update_player_walking_animation(
     for (mut player_indices, mut timer, mut atlas, player_direction, player_action) in &mut query {
         timer.tick(time.delta());
         if timer.just_finished() && PlayerAction::Walking == *player_action {
-            match player_direction {
-                Direction::Up => {
-                    atlas.index = player_indices.up.next().expect("looping iterator");
-                }
-                Direction::Down => {
-                    atlas.index = player_indices.down.next().expect("looping iterator");
-                }
-                Direction::Left => {
-                    atlas.index = player_indices.left.next().expect("looping iterator");
-                }
-                Direction::Right => {
-                    atlas.index = player_indices.right.next().expect("looping iterator");
-                }
-            }
+            atlas.index = player_indices
+                .walking(player_direction)
+                .next()
+                .expect("looping iterator");
         }
     }
 }
@@ -188,20 +451,10 @@ fn update_player_idle_animation(
     for (mut player_indices, mut timer, mut atlas, player_direction, player_action) in &mut query {
         timer.tick(time.delta());
         if timer.just_finished() && PlayerAction::Idle == *player_action {
-            match player_direction {
-                Direction::Up => {
-                    atlas.index = player_indices.idle_up.next().expect("looping iterator");
-                }
-                Direction::Down => {
-                    atlas.index = player_indices.idle_down.next().expect("looping iterator");
-                }
-                Direction::Left => {
-                    atlas.index = player_indices.idle_left.next().expect("looping iterator");
-                }
-                Direction::Right => {
-                    atlas.index = player_indices.idle_right.next().expect("looping iterator");
-                }
-            }
+            atlas.index = player_indices
+                .idle(player_direction)
+                .next()
+                .expect("looping iterator");
         }
     }
 }
@@ -220,109 +473,245 @@ fn update_idle_player_atlas(
 ) {
     for (mut player_indices, mut atlas, player_direction, player_action) in &mut query {
         if *player_action == PlayerAction::Idle {
-            match player_direction {
-                Direction::Up => {
-                    atlas.index = player_indices.idle_up.next().expect("looping iterator");
-                }
-                Direction::Down => {
-                    atlas.index = player_indices.idle_down.next().expect("looping iterator");
-                }
-                Direction::Left => {
-                    atlas.index = player_indices.idle_left.next().expect("looping iterator");
-                }
-                Direction::Right => {
-                    atlas.index = player_indices.idle_right.next().expect("looping iterator");
-                }
-            }
+            atlas.index = player_indices
+                .idle(player_direction)
+                .next()
+                .expect("looping iterator");
+        }
+    }
+}
+
+fn update_player_death_animation(
+    mut query: Query<
+        (
+            &mut PlayerAnimationIndecies,
+            &AnimationTimer,
+            &mut TextureAtlas,
+            &PlayerAction,
+        ),
+        With<Player>,
+    >,
+) {
+    for (mut player_indices, timer, mut atlas, player_action) in &mut query {
+        // the walking animation ticks the shared timer, the last frame is held once played
+        if timer.just_finished()
+            && *player_action == PlayerAction::Dead
+            && !player_indices.dead.cycle_finished()
+        {
+            atlas.index = player_indices.dead.next().expect("looping iterator");
+        }
+    }
+}
+
+fn stash_players(
+    mut stashed: ResMut<StashedPlayers>,
+    players: Query<(&PlayerId, &Inventory, &Equipment, &Experience), With<Player>>,
+) {
+    stashed.0 = players
+        .iter()
+        .map(|(id, inventory, equipment, experience)| {
+            let player = StashedPlayer {
+                inventory: inventory.clone(),
+                equipment: equipment.clone(),
+                experience: experience.clone(),
+            };
+            (*id, player)
+        })
+        .collect();
+}
+
+// players joining for the first time start out empty handed
+fn unstash_players(
+    mut commands: Commands,
+    mut stashed: ResMut<StashedPlayers>,
+    players: Query<(Entity, &PlayerId), (With<Player>, Without<Inventory>)>,
+) {
+    for (entity, id) in players.iter() {
+        let StashedPlayer {
+            inventory,
+            equipment,
+            experience,
+        } = stashed.0.remove(id).unwrap_or_default();
+        commands
+            .entity(entity)
+            .insert((inventory, equipment, experience));
+    }
+    stashed.0.clear();
+}
+
+fn kill_player(
+    mut commands: Commands,
+    mut players: Query<(Entity, &Health, &mut PlayerAction), With<Player>>,
+) {
+    for (entity, health, mut player_action) in &mut players {
+        if health.current_health <= 0 && *player_action != PlayerAction::Dead {
+            *player_action = PlayerAction::Dead;
+            // enemies stop taking turns once no player is left in the turn queue
+            commands
+                .entity(entity)
+                .remove::<Actor>()
+                .insert(DeathTimer(Timer::from_seconds(
+                    DEATH_DELAY,
+                    TimerMode::Once,
+                )));
         }
     }
 }
 
+// the run is over once the last player standing has died
+fn finish_player_death(
+    mut death_timers: Query<&mut DeathTimer, With<Player>>,
+    players: Query<&PlayerAction, With<Player>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    time: Res<Time>,
+) {
+    let all_dead = players
+        .iter()
+        .all(|player_action| *player_action == PlayerAction::Dead);
+
+    for mut death_timer in &mut death_timers {
+        death_timer.tick(time.delta());
+        if death_timer.just_finished() && all_dead {
+            next_state.set(AppState::GameOver);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn update_player_position(
-    mut players: Query<(&mut GridCoords, &mut Direction, &mut PlayerAction), With<Player>>,
+    mut players: Query<
+        (
+            Entity,
+            &mut GridCoords,
+            &mut Direction,
+            &mut PlayerAction,
+            &mut ActionTimer,
+            &StatusEffects,
+        ),
+        With<Player>,
+    >,
     mut move_direction_er: EventReader<PlayerMove>,
-    mut action_timer: Query<&mut ActionTimer, With<Player>>,
+    mut player_acted_ew: EventWriter<PlayerActed>,
     colliders: Query<&Collider, Without<Player>>,
-    mut turn_over_ew: EventWriter<TurnOver>,
+    mut turn_queue: ResMut<TurnQueue>,
     level_walls: Res<LevelWalls>,
     time: Res<Time>,
 ) {
-    let (mut player_pos, mut player_direction, mut player_action) = get_single_mut!(players);
-
-    let mut action_timer = get_single_mut!(action_timer);
+    // older moves are stale by now, e.g. sent while it was not the players turn
+    let moves: HashMap<Entity, GridCoords> = move_direction_er
+        .read()
+        .map(|player_move| (player_move.player, player_move.direction))
+        .collect();
+
+    // dead players don't block the way
+    let player_coords: Vec<(Entity, GridCoords)> = players
+        .iter()
+        .filter(|(_, _, _, player_action, ..)| **player_action != PlayerAction::Dead)
+        .map(|(entity, coords, ..)| (entity, *coords))
+        .collect();
+
+    // other players block the way just like enemies do
+    let occupied = |player_entity: Entity, destination: GridCoords| {
+        colliders
+            .iter()
+            .any(|collider| collider.get_occupied_coords().contains(&destination))
+            || player_coords
+                .iter()
+                .any(|(other, coords)| *other != player_entity && *coords == destination)
+    };
 
-    action_timer.tick(time.delta());
+    for (
+        player_entity,
+        mut player_pos,
+        mut player_direction,
+        mut player_action,
+        mut action_timer,
+        status_effects,
+    ) in players.iter_mut()
+    {
+        action_timer.tick(time.delta());
+
+        let Some(&move_direction) = moves.get(&player_entity) else {
+            continue;
+        };
 
-    let event = move_direction_er.read().next();
-    let move_direction = match event {
-        Some(PlayerMove(direction)) => *direction,
-        None => return,
-    };
+        if matches!(*player_action, PlayerAction::Combat | PlayerAction::Dead) {
+            continue;
+        }
 
-    if *player_action == PlayerAction::Combat {
-        return;
-    }
+        if !turn_queue.is_turn_of(player_entity) {
+            continue;
+        }
 
-    //If the player was idling, we want to start walking immediately and not wait for the action timer to finish
-    if *player_action == PlayerAction::Idle {
-        *player_direction = if let Ok(direction) = Direction::try_from(move_direction) {
-            direction
+        // rooted players stay where they are, like walking into a wall, and only wait out the turn
+        let rooted = status_effects.has(StatusKind::Root);
+        let cost = if rooted {
+            ActionCost::Wait
         } else {
-            return;
+            ActionCost::Move
         };
 
-        *player_action = PlayerAction::Walking;
-        turn_over_ew.send(TurnOver(PlayerAction::Walking));
-
-        let destination = *player_pos + move_direction;
+        //If the player was idling, we want to start walking immediately and not wait for the action timer to finish
+        if *player_action == PlayerAction::Idle {
+            *player_direction = if let Ok(direction) = Direction::try_from(move_direction) {
+                direction
+            } else {
+                continue;
+            };
+
+            *player_action = PlayerAction::Walking;
+            turn_queue.end_turn(player_entity, cost);
+            player_acted_ew.send(PlayerActed {
+                player: player_entity,
+                action: PlayerTurnAction::Move(move_direction.into()),
+            });
+
+            let destination = *player_pos + move_direction;
+
+            if rooted || occupied(player_entity, destination) {
+                continue;
+            }
 
-        for collider in colliders.iter() {
-            if collider
-                .get_occupied_coords()
-                .iter()
-                .any(|&coords| coords == destination)
+            // like walking into a wall, squeezing past a corner still costs the turn
+            if !level_walls.in_wall(&destination)
+                && !level_walls.cuts_corner(&player_pos, move_direction)
             {
-                return;
+                *player_pos = destination;
             }
-        }
 
-        if !level_walls.in_wall(&destination) {
-            *player_pos = destination;
+            //reset the action timer to prevent the player from moving twice, if the action timer would
+            //finish right after the player started moving from idle
+            action_timer.reset();
         }
 
-        //reset the action timer to prevent the player from moving twice, if the action timer would
-        //finish right after the player started moving from idle
-        action_timer.reset();
-    }
+        if action_timer.finished() {
+            match Direction::try_from(move_direction) {
+                Ok(direction) => {
+                    *player_direction = direction;
 
-    if action_timer.finished() {
-        match Direction::try_from(move_direction) {
-            Ok(direction) => {
-                *player_direction = direction;
+                    *player_action = PlayerAction::Walking;
+                    turn_queue.end_turn(player_entity, cost);
+                    player_acted_ew.send(PlayerActed {
+                        player: player_entity,
+                        action: PlayerTurnAction::Move(move_direction.into()),
+                    });
 
-                *player_action = PlayerAction::Walking;
-                turn_over_ew.send(TurnOver(PlayerAction::Walking));
+                    let destination = *player_pos + move_direction;
 
-                let destination = *player_pos + move_direction;
+                    if rooted || occupied(player_entity, destination) {
+                        continue;
+                    }
 
-                for collider in colliders.iter() {
-                    if collider
-                        .get_occupied_coords()
-                        .iter()
-                        .any(|&coords| coords == destination)
+                    if !level_walls.in_wall(&destination)
+                        && !level_walls.cuts_corner(&player_pos, move_direction)
                     {
-                        return;
+                        *player_pos = destination;
                     }
                 }
-
-                if !level_walls.in_wall(&destination) {
-                    *player_pos = destination;
-                }
-            }
-            Err(_) => {
-                if *player_action != PlayerAction::Idle {
-                    *player_action = PlayerAction::Idle;
-                    turn_over_ew.send(TurnOver(PlayerAction::Idle));
+                Err(_) => {
+                    if *player_action != PlayerAction::Idle {
+                        *player_action = PlayerAction::Idle;
+                    }
                 }
             }
         }
@@ -0,0 +1,204 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{
+        ability::{AbilityAssets, AbilityDefinition, AbilitySlots},
+        stats::{update_stats, BaseStats, Stats},
+    },
+    enemy::EnemyDied,
+    player::{Player, PLAYER_STATS},
+    AppState,
+};
+
+/// Experience players earn by killing enemies. Every level makes them stronger and may teach
+/// them a new ability. Levels are carried down the stairs with the rest of the player, see
+/// [`crate::player::PlayerPlugin`].
+pub struct ProgressionPlugin;
+
+impl Plugin for ProgressionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LeveledUp>()
+            .add_systems(
+                Update,
+                (award_experience, grow_stats, unlock_abilities)
+                    .chain()
+                    .before(update_stats)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .register_type::<Experience>();
+    }
+}
+
+pub const STARTING_LEVEL: u32 = 1;
+// experience the first level up takes, every level after takes this much more
+const EXPERIENCE_PER_LEVEL: u32 = 100;
+/// What a player gains on every level up.
+pub const LEVEL_UP_STATS: Stats = Stats {
+    attack: 2,
+    defense: 1,
+    spell_power: 5,
+    max_health: 10,
+};
+
+/// Level of a player and the experience collected towards the next one.
+#[derive(Component, Clone, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub struct Experience {
+    pub level: u32,
+    pub points: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: STARTING_LEVEL,
+            points: 0,
+        }
+    }
+}
+
+impl Experience {
+    /// Points needed to reach the next level.
+    pub fn next_level(&self) -> u32 {
+        EXPERIENCE_PER_LEVEL * self.level
+    }
+
+    /// Adds `points`, levelling up as often as they are enough for. Returns the number of
+    /// levels gained.
+    pub fn gain(&mut self, points: u32) -> u32 {
+        self.points += points;
+
+        let mut levels = 0;
+        while self.points >= self.next_level() {
+            self.points -= self.next_level();
+            self.level += 1;
+            levels += 1;
+        }
+        levels
+    }
+
+    /// Base stats of a player at this level.
+    pub fn base_stats(&self) -> Stats {
+        (STARTING_LEVEL..self.level).fold(PLAYER_STATS, |stats, _| stats + LEVEL_UP_STATS)
+    }
+}
+
+/// Sent when a player reached a new `level`.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct LeveledUp {
+    pub player: Entity,
+    pub level: u32,
+}
+
+// enemies killed by something else than a player, or by nothing at all, are worth nothing
+fn award_experience(
+    mut enemy_died_er: EventReader<EnemyDied>,
+    mut players: Query<&mut Experience, With<Player>>,
+    mut leveled_up_ew: EventWriter<LeveledUp>,
+) {
+    for enemy_died in enemy_died_er.read() {
+        let Some(killer) = enemy_died.killer else {
+            continue;
+        };
+        let Ok(mut experience) = players.get_mut(killer) else {
+            continue;
+        };
+
+        if experience.gain(enemy_died.experience) > 0 {
+            info!("Reached level {}", experience.level);
+            leveled_up_ew.send(LeveledUp {
+                player: killer,
+                level: experience.level,
+            });
+        }
+    }
+}
+
+// also runs when the experience is added on a new level or restored from a save
+fn grow_stats(mut players: Query<(&Experience, &mut BaseStats), Changed<Experience>>) {
+    for (experience, mut base_stats) in players.iter_mut() {
+        base_stats.set_if_neq(BaseStats(experience.base_stats()));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn unlock_abilities(
+    mut players: Query<
+        (&Experience, &mut AbilitySlots),
+        Or<(Changed<Experience>, Added<AbilitySlots>)>,
+    >,
+    ability_assets: Res<AbilityAssets>,
+    definitions: Res<Assets<AbilityDefinition>>,
+) {
+    for (experience, mut slots) in players.iter_mut() {
+        for handle in &ability_assets.abilities {
+            let Some(definition) = definitions.get(handle) else {
+                continue;
+            };
+            if definition.unlock_level > experience.level || slots.contains(handle) {
+                continue;
+            }
+
+            match slots.unlock(handle, definition) {
+                Some(slot) => info!("Learned {} in slot {}", definition.name, slot + 1),
+                None => warn!("No free slot left to learn {}", definition.name),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_below_the_next_level_are_kept() {
+        let mut experience = Experience::default();
+
+        assert_eq!(experience.gain(EXPERIENCE_PER_LEVEL - 1), 0);
+        assert_eq!(experience.level, STARTING_LEVEL);
+        assert_eq!(experience.points, EXPERIENCE_PER_LEVEL - 1);
+    }
+
+    #[test]
+    fn every_level_takes_more_points() {
+        let mut experience = Experience::default();
+
+        assert_eq!(experience.gain(EXPERIENCE_PER_LEVEL), 1);
+        assert_eq!(experience.points, 0);
+        assert_eq!(experience.next_level(), 2 * EXPERIENCE_PER_LEVEL);
+    }
+
+    #[test]
+    fn one_gain_can_level_up_several_times() {
+        let mut experience = Experience::default();
+
+        // 100 for the second level, 200 for the third, 50 left towards the fourth
+        assert_eq!(experience.gain(350), 2);
+        assert_eq!(experience.level, STARTING_LEVEL + 2);
+        assert_eq!(experience.points, 50);
+    }
+
+    #[test]
+    fn leftover_points_count_towards_the_next_gain() {
+        let mut experience = Experience::default();
+        experience.gain(60);
+
+        assert_eq!(experience.gain(60), 1);
+        assert_eq!(experience.points, 20);
+    }
+
+    #[test]
+    fn base_stats_grow_with_every_level() {
+        assert_eq!(Experience::default().base_stats(), PLAYER_STATS);
+
+        let experience = Experience {
+            level: STARTING_LEVEL + 3,
+            points: 0,
+        };
+        assert_eq!(
+            experience.base_stats(),
+            PLAYER_STATS + LEVEL_UP_STATS + LEVEL_UP_STATS + LEVEL_UP_STATS
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::CastAbility,
+    events::{PlayerActed, PlayerTurnAction, RestartRun},
+    item::{DropItem, UseItem},
+    player::{LocalPlayers, Player, PlayerId, PlayerMove, MAX_LOCAL_PLAYERS},
+    rng::{GameRng, RunSeed},
+    save::{PendingSave, SaveError},
+    turn::{TurnQueue, TurnSet},
+    AppState,
+};
+
+/// Records the actions players spend their turns on, together with the run seed, and writes
+/// them to `recording` once the run ends or the game is closed. With a `replay`, that recording
+/// is played back instead of reading input, until it runs out. Playback runs in the turns of the
+/// fixed schedule, so it doesn't depend on the frame rate. Add it after the
+/// [`crate::rng::RngPlugin`] and [`crate::player::PlayerPlugin`], a replay brings its own seed
+/// and number of players.
+pub struct ReplayPlugin {
+    pub replay: Option<PathBuf>,
+    pub recording: PathBuf,
+}
+
+impl Default for ReplayPlugin {
+    fn default() -> Self {
+        Self {
+            replay: None,
+            recording: PathBuf::from("replay.ron"),
+        }
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match self.replay.as_deref().map(Recording::read) {
+            Some(Ok(recording)) => {
+                info!(
+                    "Replaying {} actions of {} players with seed {}",
+                    recording.actions.len(),
+                    recording.players,
+                    recording.seed
+                );
+                app.insert_resource(RunSeed(Some(recording.seed)))
+                    .insert_resource(LocalPlayers(recording.players.clamp(1, MAX_LOCAL_PLAYERS)))
+                    .insert_resource(GameRng::new(recording.seed))
+                    .insert_resource(Replay::new(recording.actions));
+            }
+            Some(Err(err)) => {
+                error!("{err}, playing without the replay");
+                app.init_resource::<InputRecording>();
+            }
+            None => {
+                app.init_resource::<InputRecording>();
+            }
+        }
+
+        app.insert_resource(RecordingFile(self.recording.clone()))
+            .add_systems(
+                FixedUpdate,
+                (
+                    feed_replay.before(TurnSet::Player),
+                    advance_replay.after(TurnSet::Player),
+                )
+                    .run_if(resource_exists::<Replay>)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    record_player_actions.run_if(resource_exists::<InputRecording>),
+                    restart_recording.run_if(on_event::<RestartRun>()),
+                    stop_recording.run_if(resource_added::<PendingSave>),
+                ),
+            )
+            .add_systems(OnEnter(AppState::GameOver), write_recording)
+            .add_systems(Last, write_recording.run_if(on_event::<AppExit>()));
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct RecordingFile(pub PathBuf);
+
+/// Everything needed to play a run again from its first turn.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    #[serde(default = "single_player")]
+    pub players: usize,
+    pub actions: Vec<RecordedAction>,
+}
+
+/// An action of a recording and the [`PlayerId`] of the player that took it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedAction {
+    pub player: usize,
+    pub action: PlayerTurnAction,
+}
+
+// recordings from before co-op
+fn single_player() -> usize {
+    1
+}
+
+impl Recording {
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        // no pretty printing, recordings are attached to bug reports and get long
+        fs::write(path, ron::ser::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SaveError> {
+        let ron = fs::read_to_string(path)?;
+        Ok(ron::from_str(&ron)?)
+    }
+}
+
+/// Actions of the current run so far. A run continued from a save can't be replayed from its
+/// start, so loading one stops the recording until the next run.
+#[derive(Resource, Debug)]
+pub struct InputRecording {
+    pub actions: Vec<RecordedAction>,
+    pub recording: bool,
+}
+
+impl Default for InputRecording {
+    fn default() -> Self {
+        Self {
+            actions: Vec::new(),
+            recording: true,
+        }
+    }
+}
+
+/// A recording being played back, takes the place of player input while it lasts.
+#[derive(Resource, Debug)]
+pub struct Replay {
+    actions: Vec<RecordedAction>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(actions: Vec<RecordedAction>) -> Self {
+        Self { actions, next: 0 }
+    }
+
+    /// The action to take next and the player to take it.
+    pub fn next_action(&self) -> Option<RecordedAction> {
+        self.actions.get(self.next).copied()
+    }
+}
+
+/// Sends the next action of the replay for the player that took it, once it's their turn and
+/// until they take it and [`advance_replay`] moves on. Playback stops if another player is up.
+#[allow(clippy::too_many_arguments)]
+fn feed_replay(
+    mut commands: Commands,
+    replay: Res<Replay>,
+    turn_queue: Res<TurnQueue>,
+    players: Query<&PlayerId, With<Player>>,
+    mut move_ew: EventWriter<PlayerMove>,
+    mut cast_ability_ew: EventWriter<CastAbility>,
+    mut use_item_ew: EventWriter<UseItem>,
+    mut drop_item_ew: EventWriter<DropItem>,
+) {
+    let Some(RecordedAction { player: id, action }) = replay.next_action() else {
+        return;
+    };
+    let Some((player, current_id)) = turn_queue
+        .current()
+        .and_then(|current| players.get(current).ok().map(|id| (current, id)))
+    else {
+        return;
+    };
+
+    // the replay has nothing to play for the player that is up now
+    if current_id.0 != id {
+        warn!(
+            "Replay desynced at action {}, expected player {id} but player {} is up, back to \
+            player input",
+            replay.next, current_id.0
+        );
+        commands.remove_resource::<Replay>();
+        return;
+    }
+
+    match action {
+        PlayerTurnAction::Move(direction) => {
+            move_ew.send(PlayerMove {
+                player,
+                direction: direction.into(),
+            });
+        }
+        PlayerTurnAction::Cast { slot, target } => {
+            cast_ability_ew.send(CastAbility {
+                caster: player,
+                slot,
+                target: target.into(),
+            });
+        }
+        PlayerTurnAction::UseItem { slot } => {
+            use_item_ew.send(UseItem { player, slot });
+        }
+        PlayerTurnAction::DropItem { slot } => {
+            drop_item_ew.send(DropItem { player, slot });
+        }
+    }
+}
+
+fn advance_replay(
+    mut commands: Commands,
+    mut player_acted_er: EventReader<PlayerActed>,
+    players: Query<&PlayerId>,
+    mut replay: ResMut<Replay>,
+) {
+    for player_acted in player_acted_er.read() {
+        let Some(action) = recorded_action(player_acted, &players) else {
+            continue;
+        };
+        if replay.next_action() != Some(action) {
+            warn!(
+                "Replay desynced at action {}, expected {:?} but got {action:?}",
+                replay.next,
+                replay.next_action()
+            );
+        }
+        replay.next += 1;
+    }
+
+    if replay.next >= replay.actions.len() {
+        info!("Replay finished, back to player input");
+        commands.remove_resource::<Replay>();
+    }
+}
+
+fn record_player_actions(
+    mut player_acted_er: EventReader<PlayerActed>,
+    players: Query<&PlayerId>,
+    mut input_recording: ResMut<InputRecording>,
+) {
+    if !input_recording.recording {
+        player_acted_er.clear();
+        return;
+    }
+
+    input_recording.actions.extend(
+        player_acted_er
+            .read()
+            .filter_map(|player_acted| recorded_action(player_acted, &players)),
+    );
+}
+
+fn recorded_action(
+    player_acted: &PlayerActed,
+    players: &Query<&PlayerId>,
+) -> Option<RecordedAction> {
+    let id = players.get(player_acted.player).ok()?;
+    Some(RecordedAction {
+        player: id.0,
+        action: player_acted.action,
+    })
+}
+
+// a new run starts a new recording, a replay only covers the run it was recorded in
+fn restart_recording(mut commands: Commands) {
+    commands.remove_resource::<Replay>();
+    commands.insert_resource(InputRecording::default());
+}
+
+fn stop_recording(mut input_recording: Option<ResMut<InputRecording>>) {
+    if let Some(input_recording) = input_recording.as_mut() {
+        input_recording.recording = false;
+    }
+}
+
+fn write_recording(
+    input_recording: Option<Res<InputRecording>>,
+    recording_file: Res<RecordingFile>,
+    game_rng: Res<GameRng>,
+    local_players: Res<LocalPlayers>,
+) {
+    let Some(input_recording) = input_recording else {
+        return;
+    };
+    if !input_recording.recording {
+        return;
+    }
+
+    let recording = Recording {
+        seed: game_rng.seed(),
+        players: local_players.0,
+        actions: input_recording.actions.clone(),
+    };
+
+    match recording.write(&recording_file.0) {
+        Ok(()) => info!("Wrote recording to {}", recording_file.0.display()),
+        Err(err) => error!("{err}"),
+    }
+}
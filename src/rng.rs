@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// Seeds the [`GameRng`] of the first run. Without a `seed` every run starts from a random one.
+pub struct RngPlugin {
+    pub seed: Option<u64>,
+}
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let run_seed = RunSeed(self.seed);
+        let game_rng = GameRng::for_run(&run_seed);
+        info!("Starting run with seed {}", game_rng.seed());
+
+        app.insert_resource(run_seed).insert_resource(game_rng);
+    }
+}
+
+/// Seed entered on the title screen or passed as `--seed`, every run uses it instead of a random
+/// one.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct RunSeed(pub Option<u64>);
+
+/// Source of all gameplay randomness, reseeded at the start of every run so a seed always
+/// replays the same run. Saved with the run, so a loaded run continues with the same rolls.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+// stream 0 is the main stream, the levels use the ones after it
+const LEVEL_STREAM_OFFSET: u64 = 1;
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn for_run(run_seed: &RunSeed) -> Self {
+        Self::new(run_seed.0.unwrap_or_else(rand::random))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Independent generator for the level at `depth`. It doesn't advance the main stream, so a
+    /// level only depends on the seed, no matter what happened on the floors above.
+    pub fn level_rng(&self, depth: usize) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(LEVEL_STREAM_OFFSET + depth as u64);
+        rng
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
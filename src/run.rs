@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{RestartRun, TurnEnded},
+    player::Player,
+    rng::{GameRng, RunSeed},
+    turn::TurnQueue,
+    AppState, LEVEL_LOADED,
+};
+
+pub struct RunPlugin;
+
+impl Plugin for RunPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .add_systems(LEVEL_LOADED, track_floor)
+            .add_systems(
+                Update,
+                (
+                    count_player_turns.run_if(in_state(AppState::InGame)),
+                    restart_run.run_if(on_event::<RestartRun>()),
+                ),
+            )
+            .register_type::<RunStats>();
+    }
+}
+
+/// Progress of the current run, shown on the game over screen.
+#[derive(Resource, Default, Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct RunStats {
+    pub floor: usize,
+    pub enemies_killed: u32,
+    pub turns_taken: u32,
+}
+
+fn track_floor(level_selection: Res<LevelSelection>, mut run_stats: ResMut<RunStats>) {
+    if let LevelSelection::Indices(indices) = level_selection.into_inner() {
+        run_stats.floor = run_stats.floor.max(indices.level + 1);
+    }
+}
+
+fn count_player_turns(
+    mut turn_ended_er: EventReader<TurnEnded>,
+    players: Query<(), With<Player>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    for TurnEnded(entity) in turn_ended_er.read() {
+        if players.contains(*entity) {
+            run_stats.turns_taken += 1;
+        }
+    }
+}
+
+fn restart_run(
+    mut restart_er: EventReader<RestartRun>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut run_stats: ResMut<RunStats>,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut next_state: ResMut<NextState<AppState>>,
+    run_seed: Res<RunSeed>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    restart_er.clear();
+
+    *level_selection = LevelSelection::index(0);
+    *run_stats = RunStats::default();
+    *game_rng = GameRng::for_run(&run_seed);
+    info!("Starting run with seed {}", game_rng.seed());
+    // the actors of the old level are despawned with it
+    turn_queue.clear();
+    next_state.set(AppState::Loading);
+}
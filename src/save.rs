@@ -0,0 +1,466 @@
+use std::{fmt, fs, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{prelude::*, utils::grid_coords_to_translation};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{ability::AbilityCooldowns, status::StatusEffects},
+    enemy::{Cornered, Enemy, EnemyBehaviorState, PatrolRoute},
+    events::{LoadRun, SaveRun},
+    item::{spawn_item, Equipment, Inventory, Item},
+    player::{Player, PlayerAction},
+    progression::Experience,
+    rng::GameRng,
+    run::RunStats,
+    turn::TurnQueue,
+    AppState, Health, GRID_SIZE,
+};
+
+/// Saves the run in progress to `path` on [`SaveRun`] and continues it from there on
+/// [`LoadRun`].
+pub struct SavePlugin {
+    pub path: PathBuf,
+}
+
+impl Default for SavePlugin {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("save.ron"),
+        }
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SaveFile(self.path.clone()))
+            .add_systems(
+                Update,
+                (
+                    save_run.run_if(on_event::<SaveRun>()),
+                    load_run.run_if(on_event::<LoadRun>()),
+                    apply_save
+                        .run_if(in_state(AppState::InGame))
+                        .run_if(resource_exists::<PendingSave>),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct SaveFile(pub PathBuf);
+
+/// Snapshot of a run, entities are identified by their LDtk iid.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub level: usize,
+    pub rng: GameRng,
+    pub run_stats: RunStats,
+    pub players: Vec<SavedPlayer>,
+    pub enemies: Vec<SavedEnemy>,
+    /// `None` in saves from before there were items, their levels keep all items.
+    #[serde(default)]
+    pub items: Option<Vec<SavedItem>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub iid: String,
+    pub coords: SavedCoords,
+    pub health: Health,
+    pub cooldowns: AbilityCooldowns,
+    #[serde(default)]
+    pub inventory: Inventory,
+    #[serde(default)]
+    pub equipment: Equipment,
+    #[serde(default)]
+    pub experience: Experience,
+    #[serde(default)]
+    pub status_effects: StatusEffects,
+}
+
+/// Enemies missing from the save were killed before it was made.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedEnemy {
+    pub iid: String,
+    pub coords: SavedCoords,
+    pub health: Health,
+    pub behavior_state: EnemyBehaviorState,
+    pub cornered: bool,
+    pub next_waypoint: usize,
+    #[serde(default)]
+    pub status_effects: StatusEffects,
+}
+
+/// Items missing from the save were picked up before it was made, items the level doesn't place
+/// were dropped by a player.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedItem {
+    pub iid: String,
+    pub coords: SavedCoords,
+    pub item: Item,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedCoords {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl From<GridCoords> for SavedCoords {
+    fn from(coords: GridCoords) -> Self {
+        Self {
+            x: coords.x,
+            y: coords.y,
+        }
+    }
+}
+
+impl From<SavedCoords> for GridCoords {
+    fn from(coords: SavedCoords) -> Self {
+        GridCoords::new(coords.x, coords.y)
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access file: {err}"),
+            SaveError::Serialize(err) => write!(f, "could not serialize: {err}"),
+            SaveError::Deserialize(err) => write!(f, "could not parse: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(err: ron::Error) -> Self {
+        SaveError::Serialize(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        SaveError::Deserialize(err)
+    }
+}
+
+impl SaveGame {
+    pub fn write(&self, save_file: &SaveFile) -> Result<(), SaveError> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(&save_file.0, ron)?;
+        Ok(())
+    }
+
+    pub fn read(save_file: &SaveFile) -> Result<Self, SaveError> {
+        let ron = fs::read_to_string(&save_file.0)?;
+        Ok(ron::from_str(&ron)?)
+    }
+}
+
+/// A loaded save waiting for its level to be spawned and patched.
+#[derive(Resource)]
+pub struct PendingSave(pub SaveGame);
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn save_run(
+    mut save_er: EventReader<SaveRun>,
+    save_file: Res<SaveFile>,
+    state: Res<State<AppState>>,
+    level_selection: Res<LevelSelection>,
+    game_rng: Res<GameRng>,
+    run_stats: Res<RunStats>,
+    players: Query<
+        (
+            &EntityIid,
+            &GridCoords,
+            &Health,
+            &PlayerAction,
+            Option<&AbilityCooldowns>,
+            Option<&Inventory>,
+            Option<&Equipment>,
+            Option<&Experience>,
+            Option<&StatusEffects>,
+        ),
+        With<Player>,
+    >,
+    enemies: Query<(
+        &EntityIid,
+        &GridCoords,
+        &Health,
+        &Enemy,
+        Has<Cornered>,
+        Option<&PatrolRoute>,
+        Option<&StatusEffects>,
+    )>,
+    items: Query<(&EntityIid, &GridCoords, &Item)>,
+) {
+    save_er.clear();
+
+    if !matches!(state.get(), AppState::InGame | AppState::Menu) {
+        warn!("Can only save while a level is played");
+        return;
+    }
+
+    // projectiles in flight are not part of the save, their damage would be lost
+    if players
+        .iter()
+        .any(|(_, _, _, action, ..)| *action == PlayerAction::Combat)
+    {
+        warn!("Can't save while an ability is cast");
+        return;
+    }
+
+    if players
+        .iter()
+        .any(|(_, _, _, action, ..)| *action == PlayerAction::Dead)
+    {
+        warn!("Can't save while a player is dead");
+        return;
+    }
+
+    let LevelSelection::Indices(indices) = level_selection.into_inner() else {
+        panic!("level selection should always be Indices in this game");
+    };
+
+    let save = SaveGame {
+        level: indices.level,
+        rng: game_rng.clone(),
+        run_stats: run_stats.clone(),
+        players: players
+            .iter()
+            .map(
+                |(
+                    iid,
+                    coords,
+                    health,
+                    _,
+                    cooldowns,
+                    inventory,
+                    equipment,
+                    experience,
+                    status_effects,
+                )| {
+                    SavedPlayer {
+                        iid: iid.as_str().to_string(),
+                        coords: (*coords).into(),
+                        health: health.clone(),
+                        cooldowns: cooldowns.cloned().unwrap_or_default(),
+                        inventory: inventory.cloned().unwrap_or_default(),
+                        equipment: equipment.cloned().unwrap_or_default(),
+                        experience: experience.cloned().unwrap_or_default(),
+                        status_effects: status_effects.cloned().unwrap_or_default(),
+                    }
+                },
+            )
+            .collect(),
+        enemies: enemies
+            .iter()
+            .map(
+                |(iid, coords, health, enemy, cornered, patrol_route, status_effects)| SavedEnemy {
+                    iid: iid.as_str().to_string(),
+                    coords: (*coords).into(),
+                    health: health.clone(),
+                    behavior_state: enemy.behavior_state,
+                    cornered,
+                    next_waypoint: patrol_route
+                        .map(|patrol_route| patrol_route.next_waypoint)
+                        .unwrap_or_default(),
+                    status_effects: status_effects.cloned().unwrap_or_default(),
+                },
+            )
+            .collect(),
+        items: Some(
+            items
+                .iter()
+                .map(|(iid, coords, item)| SavedItem {
+                    iid: iid.as_str().to_string(),
+                    coords: (*coords).into(),
+                    item: *item,
+                })
+                .collect(),
+        ),
+    };
+
+    match save.write(&save_file) {
+        Ok(()) => info!("Saved run to {}", save_file.0.display()),
+        Err(err) => error!("{err}"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_run(
+    mut commands: Commands,
+    mut load_er: EventReader<LoadRun>,
+    save_file: Res<SaveFile>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut game_rng: ResMut<GameRng>,
+    mut run_stats: ResMut<RunStats>,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    load_er.clear();
+
+    let save = match SaveGame::read(&save_file) {
+        Ok(save) => save,
+        Err(err) => {
+            error!("{err}");
+            return;
+        }
+    };
+
+    *level_selection = LevelSelection::index(save.level);
+    *game_rng = save.rng.clone();
+    *run_stats = save.run_stats.clone();
+    // the actors of the old level are despawned with it
+    turn_queue.clear();
+    commands.insert_resource(PendingSave(save));
+    next_state.set(AppState::Loading);
+}
+
+// runs once the level is back in game, after the patches gave everything its default state
+#[allow(clippy::type_complexity)]
+fn apply_save(
+    mut commands: Commands,
+    pending_save: Res<PendingSave>,
+    mut players: Query<
+        (
+            &EntityIid,
+            &mut GridCoords,
+            &mut Transform,
+            &mut Health,
+            &mut AbilityCooldowns,
+            &mut Inventory,
+            &mut Equipment,
+            &mut Experience,
+            &mut StatusEffects,
+            Option<&Parent>,
+        ),
+        With<Player>,
+    >,
+    mut enemies: Query<
+        (
+            Entity,
+            &EntityIid,
+            &mut GridCoords,
+            &mut Transform,
+            &mut Health,
+            &mut Enemy,
+            &mut StatusEffects,
+            Option<&mut PatrolRoute>,
+        ),
+        Without<Player>,
+    >,
+    items: Query<(Entity, &EntityIid), With<Item>>,
+) {
+    // the level or its patches are not there yet
+    if players.is_empty() {
+        return;
+    }
+
+    let PendingSave(save) = pending_save.into_inner();
+
+    let mut level = None;
+    for (
+        iid,
+        mut coords,
+        mut transform,
+        mut health,
+        mut cooldowns,
+        mut inventory,
+        mut equipment,
+        mut experience,
+        mut status_effects,
+        parent,
+    ) in players.iter_mut()
+    {
+        level = level.or(parent.map(|parent| parent.get()));
+        let Some(saved) = save.players.iter().find(|saved| saved.iid == iid.as_str()) else {
+            continue;
+        };
+
+        *coords = saved.coords.into();
+        snap_to_coords(&mut transform, *coords);
+        *health = saved.health.clone();
+        *cooldowns = saved.cooldowns.clone();
+        *inventory = saved.inventory.clone();
+        // the saved health already counts the bonus of the equipment
+        *equipment = saved.equipment.clone();
+        // so is the growth of the level, the learned abilities follow from it
+        *experience = saved.experience.clone();
+        *status_effects = saved.status_effects.clone();
+    }
+
+    for (
+        entity,
+        iid,
+        mut coords,
+        mut transform,
+        mut health,
+        mut enemy,
+        mut status_effects,
+        patrol_route,
+    ) in enemies.iter_mut()
+    {
+        let Some(saved) = save.enemies.iter().find(|saved| saved.iid == iid.as_str()) else {
+            // despawned directly, dying again would count the kill twice
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        *coords = saved.coords.into();
+        snap_to_coords(&mut transform, *coords);
+        *health = saved.health.clone();
+        enemy.behavior_state = saved.behavior_state;
+        *status_effects = saved.status_effects.clone();
+        if saved.cornered {
+            commands.entity(entity).insert(Cornered);
+        }
+        if let Some(mut patrol_route) = patrol_route {
+            patrol_route.next_waypoint = saved.next_waypoint;
+        }
+    }
+
+    if let Some(saved_items) = &save.items {
+        for (entity, iid) in items.iter() {
+            if !saved_items.iter().any(|saved| saved.iid == iid.as_str()) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+
+        for saved in saved_items {
+            if !items.iter().any(|(_, iid)| iid.as_str() == saved.iid) {
+                spawn_item(
+                    &mut commands,
+                    saved.item,
+                    saved.coords.into(),
+                    Some(EntityIid::new(saved.iid.clone())),
+                    level,
+                );
+            }
+        }
+    }
+
+    commands.remove_resource::<PendingSave>();
+    info!("Loaded run on floor {}", save.level + 1);
+}
+
+// loaded entities would otherwise slide over from where the level placed them
+fn snap_to_coords(transform: &mut Transform, coords: GridCoords) {
+    transform.translation =
+        grid_coords_to_translation(coords, IVec2::splat(GRID_SIZE)).extend(transform.translation.z);
+}
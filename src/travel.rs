@@ -0,0 +1,555 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{utils::grid_coords_to_translation, GridCoords};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    enemy::{in_detection_range, Enemy},
+    fov::FieldOfView,
+    get_single,
+    grid::{Collider, GridPosition},
+    input::{move_player, PlayerInputAction},
+    item::Item,
+    ldtk::{LevelFloor, LevelWalls},
+    player::{Player, PlayerMove, PrimaryPlayer},
+    replay::Replay,
+    turn::Actor,
+    ui::game_cursor::{CursorPos, GameCursor},
+    AppState, Health, GRID_SIZE, PATH_PREVIEW_Z_INDEX,
+};
+
+/// Walks players with an [`AutoPath`] along it, one tile per turn. The explore action keeps
+/// giving players a path to the closest unexplored tile.
+pub struct TravelPlugin;
+
+impl Plugin for TravelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (toggle_auto_explore, auto_explore, follow_auto_path)
+                .chain()
+                .after(move_player)
+                .run_if(in_state(AppState::InGame))
+                .run_if(not(resource_exists::<Replay>)),
+        );
+    }
+}
+
+/// Left-click on an explored floor tile to walk the [`PrimaryPlayer`] there, the path is previewed
+/// under the cursor. Left out when running headless.
+pub struct ClickToMovePlugin;
+
+impl Plugin for ClickToMovePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PathPreview>()
+            .add_systems(
+                Update,
+                (
+                    click_to_move.before(follow_auto_path),
+                    update_path_preview,
+                    draw_path_preview.run_if(resource_changed::<PathPreview>),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnExit(AppState::InGame), clear_path_preview);
+    }
+}
+
+const PREVIEW_MARKER_SIZE: f32 = 4.0;
+const PREVIEW_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.4);
+const WALKING_COLOR: Color = Color::srgba(1.0, 0.85, 0.3, 0.7);
+
+/// Tiles a player still has to walk, the first one is the next step. Removed once the
+/// destination is reached, a direction is pressed, the path is blocked or a new enemy comes
+/// into view.
+#[derive(Component, Debug)]
+pub struct AutoPath {
+    pub steps: VecDeque<GridCoords>,
+    // enemies that were already in view when the walk started don't interrupt it
+    known_enemies: HashSet<Entity>,
+}
+
+impl AutoPath {
+    pub fn new(steps: VecDeque<GridCoords>, known_enemies: HashSet<Entity>) -> Self {
+        Self {
+            steps,
+            known_enemies,
+        }
+    }
+}
+
+/// Keeps a player walking towards the closest unexplored floor tile. Stops once an enemy gets
+/// close or comes into view, the player loses health, an item comes into view or nothing is
+/// left to explore.
+#[derive(Component, Debug)]
+pub struct AutoExplore {
+    // health after the last step, any loss stops exploring
+    health: i32,
+    // the tile the current path leads to, the next one is picked once it has been seen
+    target: Option<GridCoords>,
+    // items that were already in view when exploring started are left for the player to pick up
+    known_items: HashSet<Entity>,
+}
+
+impl AutoExplore {
+    pub fn new(health: i32, known_items: HashSet<Entity>) -> Self {
+        Self {
+            health,
+            target: None,
+            known_items,
+        }
+    }
+}
+
+/// Path shown from the player to the hovered tile, or the rest of the path being walked.
+#[derive(Resource, Default, Debug, PartialEq)]
+pub struct PathPreview {
+    pub steps: Vec<GridCoords>,
+    pub walking: bool,
+}
+
+#[derive(Component)]
+struct PathPreviewMarker;
+
+/// Path from `from` to `target` around walls and `occupied_coords`, without the tile the walk
+/// starts on. Only explored floor tiles can be travelled to.
+pub fn travel_path(
+    from: &GridCoords,
+    target: GridCoords,
+    field_of_view: &FieldOfView,
+    level_floor: &LevelFloor,
+    level_walls: &LevelWalls,
+    occupied_coords: &[GridCoords],
+) -> Option<VecDeque<GridCoords>> {
+    if target == *from
+        || !level_floor.floor_locations.contains(&target)
+        || !field_of_view.explored.contains(&target)
+    {
+        return None;
+    }
+
+    let path = GridPosition::new(*from).pathfind(target, level_walls, Some(occupied_coords))?;
+    Some(path.into_iter().skip(1).collect())
+}
+
+/// Path to the closest floor tile that hasn't been explored yet. Only explored tiles are walked
+/// through on the way, what lies behind unexplored ones is not known yet.
+pub fn nearest_unexplored(
+    from: &GridCoords,
+    field_of_view: &FieldOfView,
+    level_floor: &LevelFloor,
+    level_walls: &LevelWalls,
+    occupied_coords: &[GridCoords],
+) -> Option<VecDeque<GridCoords>> {
+    let mut came_from = HashMap::from([(*from, *from)]);
+    let mut frontier = VecDeque::from([*from]);
+
+    while let Some(coords) = frontier.pop_front() {
+        if !field_of_view.explored.contains(&coords) {
+            if coords == *from || !level_floor.floor_locations.contains(&coords) {
+                continue;
+            }
+
+            let mut steps = VecDeque::new();
+            let mut step = coords;
+            while step != *from {
+                steps.push_front(step);
+                step = came_from[&step];
+            }
+            return Some(steps);
+        }
+
+        for successor in
+            GridPosition::new(coords).successors(&coords, level_walls, Some(occupied_coords))
+        {
+            let next = successor.coords.0;
+            if level_walls.in_wall(&next) || came_from.contains_key(&next) {
+                continue;
+            }
+
+            came_from.insert(next, coords);
+            frontier.push_back(next);
+        }
+    }
+
+    None
+}
+
+/// Enemies or items on tiles the player currently sees.
+pub fn in_view<'a>(
+    field_of_view: &'a FieldOfView,
+    entities: impl Iterator<Item = (Entity, &'a GridCoords)> + 'a,
+) -> impl Iterator<Item = Entity> + 'a {
+    entities
+        .filter(|(_, coords)| field_of_view.visible.contains(coords))
+        .map(|(entity, _)| entity)
+}
+
+// enemies and the other players are in the way of `player`, dead ones left the turn queue and
+// don't block anyone
+fn occupied_coords(
+    colliders: &Query<&Collider, Without<Player>>,
+    players: &[(Entity, GridCoords)],
+    player: Entity,
+) -> Vec<GridCoords> {
+    colliders
+        .iter()
+        .flat_map(|collider| collider.get_occupied_coords())
+        .chain(
+            players
+                .iter()
+                .filter(|(other, _)| *other != player)
+                .map(|(_, coords)| *coords),
+        )
+        .collect()
+}
+
+fn toggle_auto_explore(
+    mut commands: Commands,
+    players: Query<
+        (
+            Entity,
+            &ActionState<PlayerInputAction>,
+            &Health,
+            &FieldOfView,
+            Has<AutoExplore>,
+        ),
+        With<Player>,
+    >,
+    items: Query<(Entity, &GridCoords), With<Item>>,
+) {
+    for (entity, action_state, health, field_of_view, exploring) in players.iter() {
+        if !action_state.just_pressed(&PlayerInputAction::Explore) {
+            continue;
+        }
+
+        if exploring {
+            commands.entity(entity).remove::<(AutoExplore, AutoPath)>();
+        } else {
+            let known_items = in_view(field_of_view, items.iter()).collect();
+            commands
+                .entity(entity)
+                .insert(AutoExplore::new(health.current_health, known_items));
+        }
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn auto_explore(
+    mut commands: Commands,
+    mut players: Query<
+        (
+            Entity,
+            &GridCoords,
+            &Health,
+            &FieldOfView,
+            &mut AutoExplore,
+            Has<AutoPath>,
+            Option<&ActionState<PlayerInputAction>>,
+        ),
+        (With<Player>, With<Actor>),
+    >,
+    enemies: Query<(Entity, &GridCoords), With<Enemy>>,
+    items: Query<(Entity, &GridCoords), With<Item>>,
+    colliders: Query<&Collider, Without<Player>>,
+    level_floor: Res<LevelFloor>,
+    level_walls: Res<LevelWalls>,
+) {
+    let player_coords: Vec<(Entity, GridCoords)> = players
+        .iter()
+        .map(|(entity, coords, ..)| (entity, *coords))
+        .collect();
+
+    for (entity, coords, health, field_of_view, mut auto_explore, walking, action_state) in
+        players.iter_mut()
+    {
+        if action_state.is_some_and(|action_state| {
+            PlayerInputAction::DIRECTIONS
+                .iter()
+                .any(|direction| action_state.pressed(direction))
+        }) {
+            commands.entity(entity).remove::<(AutoExplore, AutoPath)>();
+            continue;
+        }
+
+        if enemies
+            .iter()
+            .any(|(_, enemy_pos)| in_detection_range(coords, enemy_pos))
+        {
+            info!("Stopped exploring, an enemy is close");
+            commands.entity(entity).remove::<(AutoExplore, AutoPath)>();
+            continue;
+        }
+
+        if health.current_health < auto_explore.health {
+            info!("Stopped exploring, took damage");
+            commands.entity(entity).remove::<(AutoExplore, AutoPath)>();
+            continue;
+        }
+        auto_explore.health = health.current_health;
+
+        if in_view(field_of_view, items.iter())
+            .any(|item| !auto_explore.known_items.contains(&item))
+        {
+            info!("Stopped exploring, found an item");
+            commands.entity(entity).remove::<(AutoExplore, AutoPath)>();
+            continue;
+        }
+
+        if walking
+            && auto_explore
+                .target
+                .is_some_and(|target| !field_of_view.explored.contains(&target))
+        {
+            continue;
+        }
+
+        match nearest_unexplored(
+            coords,
+            field_of_view,
+            &level_floor,
+            &level_walls,
+            &occupied_coords(&colliders, &player_coords, entity),
+        ) {
+            Some(steps) => {
+                auto_explore.target = steps.back().copied();
+                let known_enemies = in_view(field_of_view, enemies.iter()).collect();
+                commands
+                    .entity(entity)
+                    .insert(AutoPath::new(steps, known_enemies));
+            }
+            None => {
+                info!("Nothing left to explore");
+                commands.entity(entity).remove::<AutoExplore>();
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn follow_auto_path(
+    mut commands: Commands,
+    mut players: Query<
+        (
+            Entity,
+            &GridCoords,
+            &mut AutoPath,
+            &FieldOfView,
+            Option<&ActionState<PlayerInputAction>>,
+        ),
+        (With<Player>, With<Actor>),
+    >,
+    enemies: Query<(Entity, &GridCoords), With<Enemy>>,
+    colliders: Query<&Collider, Without<Player>>,
+    level_walls: Res<LevelWalls>,
+    mut player_move_ew: EventWriter<PlayerMove>,
+) {
+    let player_coords: Vec<(Entity, GridCoords)> = players
+        .iter()
+        .map(|(entity, coords, ..)| (entity, *coords))
+        .collect();
+
+    for (entity, coords, mut auto_path, field_of_view, action_state) in players.iter_mut() {
+        // moving by hand takes over
+        if action_state.is_some_and(|action_state| {
+            PlayerInputAction::DIRECTIONS
+                .iter()
+                .any(|direction| action_state.pressed(direction))
+        }) {
+            commands.entity(entity).remove::<AutoPath>();
+            continue;
+        }
+
+        while auto_path.steps.front() == Some(coords) {
+            auto_path.steps.pop_front();
+        }
+
+        let Some(next) = auto_path.steps.front().copied() else {
+            commands.entity(entity).remove::<AutoPath>();
+            continue;
+        };
+
+        if in_view(field_of_view, enemies.iter())
+            .any(|enemy| !auto_path.known_enemies.contains(&enemy))
+        {
+            info!("Stopped walking, an enemy came into view");
+            // exploring would only head off again, the player decides what to do about it
+            commands.entity(entity).remove::<(AutoExplore, AutoPath)>();
+            continue;
+        }
+
+        let step = next - *coords;
+        let blocked = step.x.abs() > 1
+            || step.y.abs() > 1
+            || level_walls.in_wall(&next)
+            || level_walls.cuts_corner(coords, step)
+            || occupied_coords(&colliders, &player_coords, entity).contains(&next);
+        if blocked {
+            info!("Stopped walking, the path is blocked");
+            commands.entity(entity).remove::<AutoPath>();
+            continue;
+        }
+
+        // sent every frame until the turn comes, like a held direction
+        player_move_ew.send(PlayerMove {
+            player: entity,
+            direction: step,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn click_to_move(
+    mut commands: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    cursor_pos: Res<CursorPos>,
+    interactions: Query<&Interaction>,
+    player: Query<(Entity, &GridCoords, &FieldOfView), With<PrimaryPlayer>>,
+    players: Query<(Entity, &GridCoords), (With<Player>, With<Actor>)>,
+    enemies: Query<(Entity, &GridCoords), With<Enemy>>,
+    colliders: Query<&Collider, Without<Player>>,
+    level_floor: Res<LevelFloor>,
+    level_walls: Res<LevelWalls>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // clicks on the HUD are not meant for the level below it
+    if interactions
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+
+    let (entity, coords, field_of_view) = get_single!(player);
+    let player_coords: Vec<(Entity, GridCoords)> = players
+        .iter()
+        .map(|(entity, coords)| (entity, *coords))
+        .collect();
+    let Some(steps) = travel_path(
+        coords,
+        cursor_pos.world_position(),
+        field_of_view,
+        &level_floor,
+        &level_walls,
+        &occupied_coords(&colliders, &player_coords, entity),
+    ) else {
+        return;
+    };
+
+    // walking somewhere else ends exploring
+    let known_enemies = in_view(field_of_view, enemies.iter()).collect();
+    commands
+        .entity(entity)
+        .remove::<AutoExplore>()
+        .insert(AutoPath::new(steps, known_enemies));
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_path_preview(
+    player: Query<(Entity, &GridCoords, &FieldOfView, Option<&AutoPath>), With<PrimaryPlayer>>,
+    players: Query<(Entity, &GridCoords), (With<Player>, With<Actor>)>,
+    cursor: Query<&Visibility, With<GameCursor>>,
+    cursor_pos: Res<CursorPos>,
+    colliders: Query<&Collider, Without<Player>>,
+    level_floor: Res<LevelFloor>,
+    level_walls: Res<LevelWalls>,
+    mut path_preview: ResMut<PathPreview>,
+    mut hovered: Local<Option<(GridCoords, GridCoords)>>,
+) {
+    let (entity, coords, field_of_view, auto_path) = get_single!(player);
+
+    if let Some(auto_path) = auto_path {
+        *hovered = None;
+        path_preview.set_if_neq(PathPreview {
+            steps: auto_path.steps.iter().copied().collect(),
+            walking: true,
+        });
+        return;
+    }
+
+    // the preview fades out together with the cursor
+    if cursor
+        .get_single()
+        .map_or(true, |visibility| *visibility == Visibility::Hidden)
+    {
+        *hovered = None;
+        path_preview.set_if_neq(PathPreview::default());
+        return;
+    }
+
+    // pathfinding again is only needed once the cursor or the player moved to another tile
+    let target = cursor_pos.world_position();
+    if *hovered == Some((*coords, target)) {
+        return;
+    }
+    *hovered = Some((*coords, target));
+
+    let player_coords: Vec<(Entity, GridCoords)> = players
+        .iter()
+        .map(|(entity, coords)| (entity, *coords))
+        .collect();
+    let steps = travel_path(
+        coords,
+        target,
+        field_of_view,
+        &level_floor,
+        &level_walls,
+        &occupied_coords(&colliders, &player_coords, entity),
+    )
+    .unwrap_or_default();
+    path_preview.set_if_neq(PathPreview {
+        steps: steps.into(),
+        walking: false,
+    });
+}
+
+fn draw_path_preview(
+    mut commands: Commands,
+    path_preview: Res<PathPreview>,
+    markers: Query<Entity, With<PathPreviewMarker>>,
+) {
+    for marker in markers.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+
+    let color = if path_preview.walking {
+        WALKING_COLOR
+    } else {
+        PREVIEW_COLOR
+    };
+
+    for coords in path_preview.steps.iter() {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(PREVIEW_MARKER_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(
+                    grid_coords_to_translation(*coords, IVec2::splat(GRID_SIZE))
+                        .extend(PATH_PREVIEW_Z_INDEX),
+                ),
+                ..default()
+            },
+            PathPreviewMarker,
+        ));
+    }
+}
+
+// the markers are not part of the level, they would outlive it
+fn clear_path_preview(
+    mut commands: Commands,
+    mut path_preview: ResMut<PathPreview>,
+    markers: Query<Entity, With<PathPreviewMarker>>,
+) {
+    for marker in markers.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+    path_preview.set_if_neq(PathPreview::default());
+}
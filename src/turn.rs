@@ -0,0 +1,315 @@
+use std::cmp::Reverse;
+
+use bevy::prelude::*;
+
+use crate::{events::TurnEnded, AppState};
+
+pub struct TurnPlugin;
+
+impl Plugin for TurnPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TurnQueue>()
+            .configure_sets(
+                FixedUpdate,
+                (TurnSet::Player, TurnSet::Enemy)
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    register_actors.before(TurnSet::Player),
+                    emit_turn_ended.after(TurnSet::Enemy),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            // removals are tracked every frame, FixedUpdate could skip the frame they happened in
+            .add_systems(Update, unregister_actors)
+            .register_type::<Actor>();
+    }
+}
+
+/// Systems that spend turns. The player acts first, enemies then use up every turn until the
+/// player is up again.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TurnSet {
+    Player,
+    Enemy,
+}
+
+pub const NORMAL_SPEED: u32 = 100;
+
+/// Anything that takes turns. An actor with twice the [`NORMAL_SPEED`] acts twice as often,
+/// `initiative` breaks ties between actors that are ready at the same time.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct Actor {
+    pub speed: u32,
+    pub initiative: i32,
+}
+
+impl Default for Actor {
+    fn default() -> Self {
+        Self {
+            speed: NORMAL_SPEED,
+            initiative: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum ActionCost {
+    Move,
+    Attack,
+    Cast,
+    Wait,
+    UseItem,
+    DropItem,
+}
+
+impl ActionCost {
+    /// Time the action takes for an actor with [`NORMAL_SPEED`]. Spells take longer than a step,
+    /// handling items is quick enough to leave time for something else.
+    pub fn time(self) -> u64 {
+        match self {
+            ActionCost::Move => 100,
+            ActionCost::Attack => 100,
+            ActionCost::Cast => 150,
+            ActionCost::Wait => 100,
+            ActionCost::UseItem => 50,
+            ActionCost::DropItem => 50,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TurnEntry {
+    entity: Entity,
+    ready_at: u64,
+    speed: u32,
+    initiative: i32,
+    order: u64,
+}
+
+impl TurnEntry {
+    fn sort_key(&self) -> (u64, Reverse<i32>, u64) {
+        (self.ready_at, Reverse(self.initiative), self.order)
+    }
+}
+
+/// Initiative queue deciding who acts next. The actor with the lowest `ready_at` time is up,
+/// ties are broken by initiative and then by the order the actors joined the queue.
+#[derive(Resource, Default, Debug)]
+pub struct TurnQueue {
+    entries: Vec<TurnEntry>,
+    next_order: u64,
+    ended: Vec<Entity>,
+}
+
+impl TurnQueue {
+    pub fn insert(&mut self, entity: Entity, actor: &Actor) {
+        if self.contains(entity) {
+            return;
+        }
+
+        // new actors join at the current time, so they don't get to catch up on turns
+        let ready_at = self.now();
+        self.entries.push(TurnEntry {
+            entity,
+            ready_at,
+            speed: actor.speed.max(1),
+            initiative: actor.initiative,
+            order: self.next_order,
+        });
+        self.next_order += 1;
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        self.entries.retain(|entry| entry.entity != entity);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.ended.clear();
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entries.iter().any(|entry| entry.entity == entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The time of the actor that is currently up.
+    pub fn now(&self) -> u64 {
+        self.entries
+            .iter()
+            .map(|entry| entry.ready_at)
+            .min()
+            .unwrap_or_default()
+    }
+
+    pub fn current(&self) -> Option<Entity> {
+        self.entries
+            .iter()
+            .min_by_key(|entry| entry.sort_key())
+            .map(|entry| entry.entity)
+    }
+
+    pub fn is_turn_of(&self, entity: Entity) -> bool {
+        self.current() == Some(entity)
+    }
+
+    /// Ends the turn of `entity` and schedules its next one based on the cost of the action it
+    /// took. Ending the turn of an actor that isn't up is ignored.
+    pub fn end_turn(&mut self, entity: Entity, cost: ActionCost) {
+        if !self.is_turn_of(entity) {
+            return;
+        }
+
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.entity == entity) {
+            entry.ready_at += cost.time() * NORMAL_SPEED as u64 / entry.speed as u64;
+            self.ended.push(entity);
+        }
+    }
+
+    /// Changes how often `entity` acts from its next turn on, e.g. while it is slowed.
+    pub fn set_speed(&mut self, entity: Entity, speed: u32) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.entity == entity) {
+            entry.speed = speed.max(1);
+        }
+    }
+
+    /// Upcoming turns in order, the current actor first.
+    pub fn order(&self) -> Vec<Entity> {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|entry| entry.sort_key());
+        entries.iter().map(|entry| entry.entity).collect()
+    }
+}
+
+fn register_actors(
+    mut turn_queue: ResMut<TurnQueue>,
+    actors: Query<(Entity, &Actor), Added<Actor>>,
+) {
+    for (entity, actor) in actors.iter() {
+        turn_queue.insert(entity, actor);
+    }
+}
+
+fn unregister_actors(mut turn_queue: ResMut<TurnQueue>, mut removed: RemovedComponents<Actor>) {
+    for entity in removed.read() {
+        turn_queue.remove(entity);
+    }
+}
+
+pub fn emit_turn_ended(
+    mut turn_queue: ResMut<TurnQueue>,
+    mut turn_ended_ew: EventWriter<TurnEnded>,
+) {
+    for entity in turn_queue.ended.drain(..) {
+        turn_ended_ew.send(TurnEnded(entity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actor(speed: u32, initiative: i32) -> Actor {
+        Actor { speed, initiative }
+    }
+
+    /// Lets whoever is up take `turns` turns of `cost` and returns who acted, in order.
+    fn take_turns(turn_queue: &mut TurnQueue, turns: usize, cost: ActionCost) -> Vec<Entity> {
+        (0..turns)
+            .map(|_| {
+                let current = turn_queue.current().expect("the queue is not empty");
+                turn_queue.end_turn(current, cost);
+                current
+            })
+            .collect()
+    }
+
+    #[test]
+    fn faster_actors_act_more_often() {
+        let fast = Entity::from_raw(1);
+        let slow = Entity::from_raw(2);
+        let mut turn_queue = TurnQueue::default();
+        turn_queue.insert(fast, &actor(NORMAL_SPEED * 2, 0));
+        turn_queue.insert(slow, &actor(NORMAL_SPEED, 0));
+
+        let order = take_turns(&mut turn_queue, 6, ActionCost::Move);
+
+        assert_eq!(order, vec![fast, slow, fast, fast, slow, fast]);
+    }
+
+    #[test]
+    fn ties_go_to_initiative_then_join_order() {
+        let first = Entity::from_raw(1);
+        let second = Entity::from_raw(2);
+        let quick = Entity::from_raw(3);
+        let mut turn_queue = TurnQueue::default();
+        turn_queue.insert(first, &actor(NORMAL_SPEED, 0));
+        turn_queue.insert(second, &actor(NORMAL_SPEED, 0));
+        turn_queue.insert(quick, &actor(NORMAL_SPEED, 10));
+
+        assert_eq!(turn_queue.order(), vec![quick, first, second]);
+        assert_eq!(
+            take_turns(&mut turn_queue, 6, ActionCost::Wait),
+            vec![quick, first, second, quick, first, second]
+        );
+    }
+
+    #[test]
+    fn action_costs_decide_when_the_next_turn_comes() {
+        let caster = Entity::from_raw(1);
+        let walker = Entity::from_raw(2);
+        let mut turn_queue = TurnQueue::default();
+        turn_queue.insert(caster, &actor(NORMAL_SPEED, 1));
+        turn_queue.insert(walker, &actor(NORMAL_SPEED, 0));
+
+        turn_queue.end_turn(caster, ActionCost::Cast);
+        // the walker steps at 0 and 100 before the cast is over at 150
+        assert_eq!(
+            take_turns(&mut turn_queue, 2, ActionCost::Move),
+            vec![walker, walker]
+        );
+        assert_eq!(turn_queue.current(), Some(caster));
+        assert_eq!(turn_queue.now(), 150);
+    }
+
+    #[test]
+    fn ending_the_turn_of_someone_else_is_ignored() {
+        let up = Entity::from_raw(1);
+        let waiting = Entity::from_raw(2);
+        let mut turn_queue = TurnQueue::default();
+        turn_queue.insert(up, &actor(NORMAL_SPEED, 1));
+        turn_queue.insert(waiting, &actor(NORMAL_SPEED, 0));
+
+        turn_queue.end_turn(waiting, ActionCost::Move);
+
+        assert_eq!(turn_queue.current(), Some(up));
+        assert_eq!(turn_queue.order(), vec![up, waiting]);
+    }
+
+    #[test]
+    fn set_speed_applies_from_the_next_turn() {
+        let slowed = Entity::from_raw(1);
+        let other = Entity::from_raw(2);
+        let mut turn_queue = TurnQueue::default();
+        turn_queue.insert(slowed, &actor(NORMAL_SPEED, 1));
+        turn_queue.insert(other, &actor(NORMAL_SPEED, 0));
+
+        turn_queue.set_speed(slowed, NORMAL_SPEED / 2);
+
+        assert_eq!(
+            take_turns(&mut turn_queue, 5, ActionCost::Move),
+            vec![slowed, other, other, slowed, other]
+        );
+    }
+}
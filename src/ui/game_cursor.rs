@@ -2,8 +2,17 @@ use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_ecs_ldtk::{utils::translation_to_grid_coords, GridCoords};
 
 use crate::{
-    camera::MainCamera, enemy::Enemy, get_single, input::move_player, player::Player, AppState,
-    CURSOR_Z_INDEX, GRID_SIZE,
+    camera::MainCamera,
+    combat::{
+        ability::{AbilityCooldowns, AbilityDefinition, AbilitySlots, Targeting},
+        cast_target,
+    },
+    enemy::Enemy,
+    get_single,
+    input::move_player,
+    ldtk::LevelWalls,
+    player::PrimaryPlayer,
+    AppState, CURSOR_Z_INDEX, GRID_SIZE,
 };
 
 pub struct GameCursorPlugin;
@@ -11,7 +20,6 @@ pub struct GameCursorPlugin;
 impl Plugin for GameCursorPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CursorPos>()
-            .add_systems(Startup, hide_grab)
             .insert_resource(CursorDirection::default())
             .add_systems(
                 FixedUpdate,
@@ -35,6 +43,11 @@ pub struct GameCursor {
 #[derive(Component, Reflect)]
 pub struct AttackCursor;
 
+/// Shown instead of the [`AttackCursor`] when no ability can currently be cast on the enemy
+/// under the cursor.
+#[derive(Component, Reflect)]
+pub struct InvalidTargetCursor;
+
 #[derive(Resource, Reflect, Default)]
 pub struct CursorPos {
     pub world_coords: Vec3,
@@ -158,11 +171,6 @@ fn update_game_cursor(
     cursor_transform.translation = cursor_pos;
 }
 
-fn hide_grab(mut q_windows: Query<&mut Window, With<PrimaryWindow>>) {
-    let mut primary_window = q_windows.single_mut();
-    primary_window.cursor.visible = false;
-}
-
 fn show_cursor(
     mut cursor_moved_er: EventReader<CursorMoved>,
     mut cursor_visivility: Query<&mut Visibility, With<GameCursor>>,
@@ -197,7 +205,10 @@ fn show_cursor(
 fn cursor_mode(
     cursor_pos: Res<CursorPos>,
     mut cursor: Query<(Entity, &mut Handle<Image>), With<GameCursor>>,
-    enemies_pos: Query<&GridCoords, With<Enemy>>,
+    enemies_pos: Query<(Entity, &GridCoords), With<Enemy>>,
+    player: Query<(&GridCoords, &AbilitySlots, &AbilityCooldowns), With<PrimaryPlayer>>,
+    definitions: Res<Assets<AbilityDefinition>>,
+    level_walls: Res<LevelWalls>,
     asset_server: Res<AssetServer>,
     mut commands: Commands,
 ) {
@@ -209,20 +220,59 @@ fn cursor_mode(
         return;
     };
 
-    for enemy_pos in enemies_pos.iter() {
-        if cursor_pos == *enemy_pos {
+    for (enemy, enemy_pos) in enemies_pos.iter() {
+        if cursor_pos != *enemy_pos {
+            continue;
+        }
+
+        let castable = player
+            .get_single()
+            .is_ok_and(|(player_pos, slots, cooldowns)| {
+                slots
+                    .0
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(slot, handle)| Some((slot, definitions.get(handle.as_ref()?)?)))
+                    .filter(|(_, definition)| definition.targeting == Targeting::Enemy)
+                    .any(|(slot, definition)| {
+                        if !cooldowns.is_ready(slot) {
+                            return false;
+                        }
+                        cast_target(
+                            definition,
+                            player_pos,
+                            &cursor_pos,
+                            Some(enemy),
+                            &level_walls,
+                        )
+                        .is_ok()
+                    })
+            });
+
+        if castable {
             *cursor_image = asset_server.load("Cursors_v2/Light/Arrows/Arrow4.png");
-            commands.entity(entity).insert(AttackCursor);
-            return;
+            commands
+                .entity(entity)
+                .remove::<InvalidTargetCursor>()
+                .insert(AttackCursor);
+        } else {
+            *cursor_image = asset_server.load("Cursors_v2/Light/Not Allowed/Not_Allowed_Thin.png");
+            commands
+                .entity(entity)
+                .remove::<AttackCursor>()
+                .insert(InvalidTargetCursor);
         }
+        return;
     }
-    commands.entity(entity).remove::<AttackCursor>();
+    commands
+        .entity(entity)
+        .remove::<(AttackCursor, InvalidTargetCursor)>();
     *cursor_image = asset_server.load("Cursors_v2/Light/Arrows/Arrow1.png");
 }
 
 fn cursor_direction(
     cursor_pos: Res<CursorPos>,
-    player_grid: Query<&GridCoords, With<Player>>,
+    player_grid: Query<&GridCoords, With<PrimaryPlayer>>,
     mut cursor_direction: ResMut<CursorDirection>,
 ) {
     let player_pos = get_single!(player_grid);
@@ -0,0 +1,138 @@
+use bevy::{color::palettes::css, prelude::*, window::PrimaryWindow};
+use leafwing_input_manager::prelude::ActionState;
+use sickle_ui::{
+    prelude::*,
+    ui_builder::{UiBuilder, UiBuilderExt, UiRoot},
+};
+
+use crate::{
+    events::RestartRun, input::MenuAction, rng::GameRng, run::RunStats, ui::widgets::widget_text,
+    AppState, GameCursor,
+};
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::GameOver), spawn_game_over_screen)
+            .add_systems(OnExit(AppState::GameOver), despawn_game_over_screen)
+            .add_systems(
+                Update,
+                restart_on_input.run_if(in_state(AppState::GameOver)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct GameOverScreen;
+
+#[derive(Component)]
+struct RestartButton;
+
+pub trait GameOverScreenExt {
+    fn game_over_screen(&mut self, run_stats: &RunStats, seed: u64) -> UiBuilder<Entity>;
+}
+
+impl GameOverScreenExt for UiBuilder<'_, UiRoot> {
+    fn game_over_screen(&mut self, run_stats: &RunStats, seed: u64) -> UiBuilder<Entity> {
+        self.container(
+            (
+                NodeBundle {
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                GameOverScreen,
+            ),
+            |screen| {
+                let entity = screen.id();
+                screen
+                    .commands()
+                    .entity(entity)
+                    .insert(Name::new("GameOverScreen"));
+
+                screen
+                    .style()
+                    .position_type(PositionType::Absolute)
+                    .width(Val::Percent(100.0))
+                    .height(Val::Percent(100.0))
+                    .flex_direction(FlexDirection::Column)
+                    .justify_content(JustifyContent::Center)
+                    .align_items(AlignItems::Center)
+                    .background_color(Color::BLACK.with_alpha(0.8));
+
+                screen.container(widget_text("You died", 48.0), |_| {});
+                screen.container(
+                    widget_text(&format!("Floor reached: {}", run_stats.floor), 24.0),
+                    |_| {},
+                );
+                screen.container(
+                    widget_text(
+                        &format!("Enemies killed: {}", run_stats.enemies_killed),
+                        24.0,
+                    ),
+                    |_| {},
+                );
+                screen.container(
+                    widget_text(&format!("Turns taken: {}", run_stats.turns_taken), 24.0),
+                    |_| {},
+                );
+                // enter it on the title screen or start the game with `--seed` to replay this run
+                screen.container(widget_text(&format!("Seed: {seed}"), 24.0), |_| {});
+
+                screen.container((ButtonBundle::default(), RestartButton), |button| {
+                    button
+                        .style()
+                        .margin(UiRect::top(Val::Px(20.0)))
+                        .padding(UiRect::all(Val::Px(10.0)))
+                        .background_color(css::CORAL.into());
+
+                    button.container(widget_text("Restart (Enter)", 24.0), |_| {});
+                });
+            },
+        )
+    }
+}
+
+fn spawn_game_over_screen(
+    mut commands: Commands,
+    run_stats: Res<RunStats>,
+    game_rng: Res<GameRng>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut game_cursor: Query<&mut Visibility, With<GameCursor>>,
+) {
+    commands
+        .ui_builder(UiRoot)
+        .game_over_screen(&run_stats, game_rng.seed());
+
+    // the restart button is clicked with the system cursor
+    windows.single_mut().cursor.visible = true;
+    if let Ok(mut game_cursor) = game_cursor.get_single_mut() {
+        *game_cursor = Visibility::Hidden;
+    }
+}
+
+fn despawn_game_over_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<GameOverScreen>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    windows.single_mut().cursor.visible = false;
+}
+
+fn restart_on_input(
+    action_state: Res<ActionState<MenuAction>>,
+    restart_button: Query<&Interaction, (Changed<Interaction>, With<RestartButton>)>,
+    mut restart_ew: EventWriter<RestartRun>,
+) {
+    let clicked = restart_button
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed);
+
+    if clicked || action_state.just_pressed(&MenuAction::Select) {
+        restart_ew.send(RestartRun);
+    }
+}
@@ -0,0 +1,631 @@
+use bevy::{
+    color::palettes::css,
+    prelude::*,
+    window::{PrimaryWindow, WindowMode},
+};
+use leafwing_input_manager::prelude::ActionState;
+use sickle_ui::{
+    prelude::*,
+    ui_builder::{UiBuilder, UiBuilderExt, UiRoot},
+};
+
+use crate::{
+    events::{LoadRun, SaveRun},
+    input::{
+        bindings::{BindingInput, Controls, ControlsFile},
+        MenuAction, PlayerInputAction,
+    },
+    rng::{GameRng, RunSeed},
+    ui::{widgets::widget_text, PlayerHud},
+    AppState, GameCursor,
+};
+
+/// Title screen shown before the first level and the pause menu, both navigated with the mouse
+/// or through [`MenuAction`].
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuFocus>()
+            .init_resource::<Rebinding>()
+            .init_resource::<SeedEntry>()
+            .add_systems(
+                OnEnter(AppState::MainMenu),
+                (open_title_menu, show_system_cursor),
+            )
+            .add_systems(
+                OnEnter(AppState::Menu),
+                (open_pause_menu, show_system_cursor),
+            )
+            .add_systems(OnExit(AppState::MainMenu), close_menu)
+            .add_systems(OnExit(AppState::Menu), close_menu)
+            .add_systems(
+                Update,
+                (
+                    spawn_menu_page.run_if(resource_exists_and_changed::<MenuPage>),
+                    capture_rebinding.run_if(resource_exists::<MenuPage>.and_then(is_rebinding)),
+                    capture_seed_entry
+                        .run_if(resource_exists::<MenuPage>.and_then(is_entering_seed)),
+                    (navigate_menu, highlight_focused_button, press_menu_button)
+                        .chain()
+                        .run_if(
+                            resource_exists::<MenuPage>
+                                .and_then(not(is_rebinding))
+                                .and_then(not(is_entering_seed)),
+                        ),
+                )
+                    .chain(),
+            )
+            .observe(on_menu_button_pressed);
+    }
+}
+
+const BUTTON_COLOR: Srgba = css::DARK_SLATE_GRAY;
+const FOCUSED_BUTTON_COLOR: Srgba = css::CORAL;
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+enum MenuPage {
+    Title,
+    Pause,
+    Settings,
+    Controls,
+}
+
+impl MenuPage {
+    fn buttons(self) -> Vec<MenuButton> {
+        match self {
+            MenuPage::Title => vec![
+                MenuButton::NewGame,
+                MenuButton::Seed,
+                MenuButton::Load,
+                MenuButton::Settings,
+                MenuButton::Quit,
+            ],
+            MenuPage::Pause => vec![
+                MenuButton::Resume,
+                MenuButton::Settings,
+                MenuButton::Save,
+                MenuButton::Load,
+                MenuButton::Quit,
+            ],
+            MenuPage::Settings => vec![
+                MenuButton::ToggleHud,
+                MenuButton::ToggleFullscreen,
+                MenuButton::Controls,
+                MenuButton::Back,
+            ],
+            MenuPage::Controls => PlayerInputAction::ALL
+                .into_iter()
+                .map(MenuButton::Rebind)
+                .chain([MenuButton::Back])
+                .collect(),
+        }
+    }
+}
+
+/// Index of the button on the current page that [`MenuAction::Select`] presses.
+#[derive(Resource, Default, Debug)]
+struct MenuFocus(usize);
+
+#[derive(Component)]
+struct MenuScreen;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+enum MenuButton {
+    NewGame,
+    Seed,
+    Resume,
+    Settings,
+    Save,
+    Load,
+    Quit,
+    ToggleHud,
+    ToggleFullscreen,
+    Controls,
+    Rebind(PlayerInputAction),
+    Back,
+}
+
+/// The action waiting for its new key or gamepad button on the controls page, and what went
+/// wrong with the last attempt to rebind.
+#[derive(Resource, Default, Debug)]
+struct Rebinding {
+    action: Option<PlayerInputAction>,
+    notice: Option<String>,
+}
+
+/// Digits of the seed being typed on the title screen, `None` while the seed field isn't
+/// focused. Confirmed with Enter into the [`RunSeed`], an empty field picks a random seed.
+#[derive(Resource, Default, Debug)]
+struct SeedEntry(Option<String>);
+
+/// Position of a button on its page.
+#[derive(Component)]
+struct MenuIndex(usize);
+
+#[derive(Event)]
+struct MenuButtonPressed(MenuButton);
+
+/// Current value of a setting, shown next to its name.
+struct SettingsState<'a> {
+    hud: bool,
+    fullscreen: bool,
+    controls: &'a Controls,
+    rebinding: &'a Rebinding,
+    seed: Option<u64>,
+    seed_entry: &'a SeedEntry,
+}
+
+impl MenuButton {
+    fn label(self, settings: &SettingsState) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+
+        match self {
+            MenuButton::NewGame => "New game".to_string(),
+            MenuButton::Seed => match (&settings.seed_entry.0, settings.seed) {
+                (Some(digits), _) => format!("Seed: {digits}_"),
+                (None, Some(seed)) => format!("Seed: {seed}"),
+                (None, None) => "Seed: Random".to_string(),
+            },
+            MenuButton::Resume => "Resume".to_string(),
+            MenuButton::Settings => "Settings".to_string(),
+            MenuButton::Save => "Save".to_string(),
+            MenuButton::Load => "Load".to_string(),
+            MenuButton::Quit => "Quit".to_string(),
+            MenuButton::ToggleHud => format!("HUD: {}", on_off(settings.hud)),
+            MenuButton::ToggleFullscreen => {
+                format!("Fullscreen: {}", on_off(settings.fullscreen))
+            }
+            MenuButton::Controls => "Controls".to_string(),
+            MenuButton::Rebind(action) if settings.rebinding.action == Some(action) => {
+                format!("{action:?}: press a key or button")
+            }
+            MenuButton::Rebind(action) => {
+                let binding = settings.controls.binding(action);
+                let mut key = binding
+                    .and_then(|binding| binding.key)
+                    .map_or("-".to_string(), |key| format!("{key:?}"));
+                // alternatives can't be rebound, they are only listed
+                if let Some(binding) = binding.filter(|binding| !binding.alternate_keys.is_empty())
+                {
+                    let alternates: Vec<String> = binding
+                        .alternate_keys
+                        .iter()
+                        .map(|key| format!("{key:?}"))
+                        .collect();
+                    key = format!("{key} ({})", alternates.join(", "));
+                }
+                let gamepad = binding
+                    .and_then(|binding| binding.gamepad)
+                    .map_or("-".to_string(), |button| format!("{button:?}"));
+                format!("{action:?}: {key} / {gamepad}")
+            }
+            MenuButton::Back => "Back".to_string(),
+        }
+    }
+}
+
+trait MenuScreenExt {
+    fn menu_screen(&mut self, page: MenuPage, settings: &SettingsState) -> UiBuilder<Entity>;
+}
+
+impl MenuScreenExt for UiBuilder<'_, UiRoot> {
+    fn menu_screen(&mut self, page: MenuPage, settings: &SettingsState) -> UiBuilder<Entity> {
+        self.container(
+            (
+                NodeBundle {
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                MenuScreen,
+            ),
+            |screen| {
+                let entity = screen.id();
+                screen
+                    .commands()
+                    .entity(entity)
+                    .insert(Name::new("MenuScreen"));
+
+                // the title screen hides the level that is already spawned behind it
+                let background = match page {
+                    MenuPage::Title => Color::BLACK,
+                    MenuPage::Pause | MenuPage::Settings | MenuPage::Controls => {
+                        Color::BLACK.with_alpha(0.8)
+                    }
+                };
+
+                screen
+                    .style()
+                    .position_type(PositionType::Absolute)
+                    .width(Val::Percent(100.0))
+                    .height(Val::Percent(100.0))
+                    .flex_direction(FlexDirection::Column)
+                    .justify_content(JustifyContent::Center)
+                    .align_items(AlignItems::Center)
+                    .background_color(background);
+
+                let heading = match page {
+                    MenuPage::Title => "Dungeon",
+                    MenuPage::Pause => "Paused",
+                    MenuPage::Settings => "Settings",
+                    MenuPage::Controls => "Controls",
+                };
+                screen.container(widget_text(heading, 48.0), |_| {});
+
+                if page == MenuPage::Title && settings.seed_entry.0.is_some() {
+                    screen.container(
+                        widget_text(
+                            "Type a seed and press Enter, leave it empty for a random one (Escape cancels)",
+                            18.0,
+                        ),
+                        |_| {},
+                    );
+                }
+
+                if page == MenuPage::Controls {
+                    let notice = settings.rebinding.notice.as_deref().unwrap_or(
+                        "Select an action, then press its new key or gamepad button (Escape cancels)",
+                    );
+                    screen.container(widget_text(notice, 18.0), |_| {});
+                }
+
+                for (index, button) in page.buttons().iter().enumerate() {
+                    screen.container(
+                        (ButtonBundle::default(), *button, MenuIndex(index)),
+                        |menu_button| {
+                            menu_button
+                                .style()
+                                .width(Val::Px(360.0))
+                                .margin(UiRect::top(Val::Px(10.0)))
+                                .padding(UiRect::all(Val::Px(10.0)))
+                                .justify_content(JustifyContent::Center)
+                                .background_color(BUTTON_COLOR.into());
+
+                            menu_button.container(widget_text(&button.label(settings), 24.0), |_| {});
+                        },
+                    );
+                }
+            },
+        )
+    }
+}
+
+fn open_title_menu(mut commands: Commands, mut menu_focus: ResMut<MenuFocus>) {
+    commands.insert_resource(MenuPage::Title);
+    menu_focus.0 = 0;
+}
+
+fn open_pause_menu(mut commands: Commands, mut menu_focus: ResMut<MenuFocus>) {
+    commands.insert_resource(MenuPage::Pause);
+    menu_focus.0 = 0;
+}
+
+// the menus are clicked with the system cursor
+fn show_system_cursor(
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut game_cursor: Query<&mut Visibility, With<GameCursor>>,
+) {
+    windows.single_mut().cursor.visible = true;
+    // the title screen opens before the game cursor is spawned
+    if let Ok(mut game_cursor) = game_cursor.get_single_mut() {
+        *game_cursor = Visibility::Hidden;
+    }
+}
+
+fn close_menu(
+    mut commands: Commands,
+    screens: Query<Entity, With<MenuScreen>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut game_cursor: Query<&mut Visibility, With<GameCursor>>,
+) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<MenuPage>();
+    commands.insert_resource(Rebinding::default());
+
+    windows.single_mut().cursor.visible = false;
+    if let Ok(mut game_cursor) = game_cursor.get_single_mut() {
+        *game_cursor = Visibility::Visible;
+    }
+}
+
+fn spawn_menu_page(
+    mut commands: Commands,
+    menu_page: Res<MenuPage>,
+    screens: Query<Entity, With<MenuScreen>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    hud: Query<&Visibility, With<PlayerHud>>,
+    controls: Res<Controls>,
+    rebinding: Res<Rebinding>,
+    run_seed: Res<RunSeed>,
+    seed_entry: Res<SeedEntry>,
+) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let settings = SettingsState {
+        hud: hud
+            .iter()
+            .next()
+            .map_or(true, |visibility| *visibility != Visibility::Hidden),
+        fullscreen: windows.single().mode != WindowMode::Windowed,
+        controls: &controls,
+        rebinding: &rebinding,
+        seed: run_seed.0,
+        seed_entry: &seed_entry,
+    };
+
+    commands
+        .ui_builder(UiRoot)
+        .menu_screen(*menu_page, &settings);
+}
+
+fn navigate_menu(
+    action_state: Res<ActionState<MenuAction>>,
+    menu_page: Res<MenuPage>,
+    hovered_buttons: Query<(&Interaction, &MenuIndex), Changed<Interaction>>,
+    mut menu_focus: ResMut<MenuFocus>,
+) {
+    let button_count = menu_page.buttons().len();
+
+    if action_state.just_pressed(&MenuAction::Down) {
+        menu_focus.0 = (menu_focus.0 + 1) % button_count;
+    }
+    if action_state.just_pressed(&MenuAction::Up) {
+        menu_focus.0 = (menu_focus.0 + button_count - 1) % button_count;
+    }
+
+    for (interaction, index) in hovered_buttons.iter() {
+        if *interaction == Interaction::Hovered {
+            menu_focus.0 = index.0;
+        }
+    }
+}
+
+fn highlight_focused_button(
+    menu_focus: Res<MenuFocus>,
+    mut buttons: Query<(Ref<MenuIndex>, &mut BackgroundColor)>,
+) {
+    for (index, mut background_color) in buttons.iter_mut() {
+        if !menu_focus.is_changed() && !index.is_added() {
+            continue;
+        }
+
+        *background_color = if index.0 == menu_focus.0 {
+            FOCUSED_BUTTON_COLOR.into()
+        } else {
+            BUTTON_COLOR.into()
+        };
+    }
+}
+
+fn press_menu_button(
+    mut commands: Commands,
+    action_state: Res<ActionState<MenuAction>>,
+    menu_page: Res<MenuPage>,
+    menu_focus: Res<MenuFocus>,
+    clicked_buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+) {
+    let clicked = clicked_buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| *button);
+
+    let selected = action_state
+        .just_pressed(&MenuAction::Select)
+        .then(|| menu_page.buttons().get(menu_focus.0).copied())
+        .flatten();
+
+    let back = if action_state.just_pressed(&MenuAction::Pause) {
+        match *menu_page {
+            MenuPage::Title => None,
+            MenuPage::Pause => Some(MenuButton::Resume),
+            MenuPage::Settings | MenuPage::Controls => Some(MenuButton::Back),
+        }
+    } else {
+        None
+    };
+
+    if let Some(button) = clicked.or(selected).or(back) {
+        commands.trigger(MenuButtonPressed(button));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn on_menu_button_pressed(
+    trigger: Trigger<MenuButtonPressed>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut menu_page: ResMut<MenuPage>,
+    mut menu_focus: ResMut<MenuFocus>,
+    mut save_ew: EventWriter<SaveRun>,
+    mut load_ew: EventWriter<LoadRun>,
+    mut exit_ew: EventWriter<AppExit>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut hud: Query<&mut Visibility, With<PlayerHud>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut seed_entry: ResMut<SeedEntry>,
+    run_seed: Res<RunSeed>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    let MenuButtonPressed(button) = trigger.event();
+
+    match button {
+        MenuButton::NewGame => {
+            // the seed may have changed since the game started
+            *game_rng = GameRng::for_run(&run_seed);
+            info!("Starting run with seed {}", game_rng.seed());
+            next_state.set(AppState::Loading);
+        }
+        MenuButton::Seed => {
+            seed_entry.0 = Some(run_seed.0.map_or(String::new(), |seed| seed.to_string()));
+            menu_page.set_changed();
+        }
+        MenuButton::Resume => next_state.set(AppState::InGame),
+        MenuButton::Settings => {
+            *menu_page = MenuPage::Settings;
+            menu_focus.0 = 0;
+        }
+        MenuButton::Save => {
+            save_ew.send(SaveRun);
+        }
+        MenuButton::Load => {
+            load_ew.send(LoadRun);
+        }
+        MenuButton::Quit => {
+            exit_ew.send(AppExit::Success);
+        }
+        MenuButton::ToggleHud => {
+            for mut visibility in hud.iter_mut() {
+                *visibility = if *visibility == Visibility::Hidden {
+                    Visibility::Visible
+                } else {
+                    Visibility::Hidden
+                };
+            }
+            // respawn the page with the new value
+            menu_page.set_changed();
+        }
+        MenuButton::ToggleFullscreen => {
+            let mut window = windows.single_mut();
+            window.mode = if window.mode == WindowMode::Windowed {
+                WindowMode::BorderlessFullscreen
+            } else {
+                WindowMode::Windowed
+            };
+            menu_page.set_changed();
+        }
+        MenuButton::Controls => {
+            *menu_page = MenuPage::Controls;
+            menu_focus.0 = 0;
+        }
+        MenuButton::Rebind(action) => {
+            rebinding.action = Some(*action);
+            rebinding.notice = None;
+            menu_page.set_changed();
+        }
+        MenuButton::Back => {
+            *menu_page = match (*menu_page, state.get()) {
+                (MenuPage::Controls, _) => MenuPage::Settings,
+                (_, AppState::MainMenu) => MenuPage::Title,
+                _ => MenuPage::Pause,
+            };
+            rebinding.notice = None;
+            menu_focus.0 = 0;
+        }
+    }
+}
+
+// also skips the frame a rebinding ends in, so the key that finished it doesn't press a button
+fn is_rebinding(rebinding: Res<Rebinding>) -> bool {
+    rebinding.action.is_some() || rebinding.is_changed()
+}
+
+fn capture_rebinding(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut controls: ResMut<Controls>,
+    controls_file: Res<ControlsFile>,
+    mut menu_page: ResMut<MenuPage>,
+) {
+    let Some(action) = rebinding.action else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        rebinding.action = None;
+        menu_page.set_changed();
+        return;
+    }
+
+    let input = keys
+        .get_just_pressed()
+        .next()
+        .map(|key| BindingInput::Key(*key))
+        .or_else(|| {
+            gamepad_buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| BindingInput::Gamepad(button.button_type))
+        });
+    let Some(input) = input else {
+        return;
+    };
+
+    rebinding.action = None;
+    rebinding.notice = match controls.rebind(action, input) {
+        Ok(()) => match controls.write(&controls_file.0) {
+            Ok(()) => None,
+            Err(err) => Some(format!("Could not save the controls: {err}")),
+        },
+        Err(conflict) => Some(format!("Can't bind {action:?}, that input is {conflict}")),
+    };
+    menu_page.set_changed();
+}
+
+// also skips the frame the seed is confirmed in, so Enter doesn't press a button
+fn is_entering_seed(seed_entry: Res<SeedEntry>) -> bool {
+    seed_entry.0.is_some() || seed_entry.is_changed()
+}
+
+fn capture_seed_entry(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut seed_entry: ResMut<SeedEntry>,
+    mut run_seed: ResMut<RunSeed>,
+    mut menu_page: ResMut<MenuPage>,
+) {
+    let Some(mut digits) = seed_entry.0.clone() else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        seed_entry.0 = None;
+        menu_page.set_changed();
+        return;
+    }
+
+    if keys.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter]) {
+        // only digits that keep it a valid seed are typed in
+        run_seed.0 = digits.parse().ok();
+        seed_entry.0 = None;
+        menu_page.set_changed();
+        return;
+    }
+
+    let mut typed = false;
+    if keys.just_pressed(KeyCode::Backspace) {
+        typed |= digits.pop().is_some();
+    }
+    for digit in keys.get_just_pressed().filter_map(|key| seed_digit(*key)) {
+        let longer = format!("{digits}{digit}");
+        if longer.parse::<u64>().is_ok() {
+            digits = longer;
+            typed = true;
+        }
+    }
+
+    if typed {
+        seed_entry.0 = Some(digits);
+        menu_page.set_changed();
+    }
+}
+
+fn seed_digit(key: KeyCode) -> Option<char> {
+    let digit = match key {
+        KeyCode::Digit0 | KeyCode::Numpad0 => 0,
+        KeyCode::Digit1 | KeyCode::Numpad1 => 1,
+        KeyCode::Digit2 | KeyCode::Numpad2 => 2,
+        KeyCode::Digit3 | KeyCode::Numpad3 => 3,
+        KeyCode::Digit4 | KeyCode::Numpad4 => 4,
+        KeyCode::Digit5 | KeyCode::Numpad5 => 5,
+        KeyCode::Digit6 | KeyCode::Numpad6 => 6,
+        KeyCode::Digit7 | KeyCode::Numpad7 => 7,
+        KeyCode::Digit8 | KeyCode::Numpad8 => 8,
+        KeyCode::Digit9 | KeyCode::Numpad9 => 9,
+        _ => return None,
+    };
+    char::from_digit(digit, 10)
+}
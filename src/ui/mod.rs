@@ -1,9 +1,16 @@
 use bevy::prelude::*;
 use game_cursor::GameCursorPlugin;
+use game_over::GameOverPlugin;
+use menu::MenuPlugin;
 use sickle_ui::SickleUiPlugin;
-use widgets::{action_bar::ActionBarPlugin, player_widget::PlayerWidgetPlugin};
+use widgets::{
+    action_bar::ActionBarPlugin, inventory_panel::InventoryPanelPlugin,
+    player_widget::PlayerWidgetPlugin,
+};
 
 pub mod game_cursor;
+pub mod game_over;
+pub mod menu;
 pub mod widgets;
 
 pub struct UiPlugin;
@@ -16,7 +23,10 @@ impl Plugin for UiPlugin {
         app.add_plugins(SickleUiPlugin)
             .add_plugins(ActionBarPlugin)
             .add_plugins(PlayerWidgetPlugin)
+            .add_plugins(InventoryPanelPlugin)
             .add_plugins(GameCursorPlugin)
+            .add_plugins(GameOverPlugin)
+            .add_plugins(MenuPlugin)
             .add_systems(Update, toggle_ui);
     }
 }
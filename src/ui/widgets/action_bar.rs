@@ -4,12 +4,17 @@ use sickle_ui::{
     ui_builder::{UiBuilder, UiBuilderExt, UiRoot},
 };
 
-use crate::ui::PlayerHud;
+use crate::{
+    combat::ability::{AbilityCooldowns, AbilityDefinition, AbilitySlots},
+    player::PrimaryPlayer,
+    ui::PlayerHud,
+};
 
 pub struct ActionBarPlugin;
 impl Plugin for ActionBarPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_action_bar_widget);
+        app.add_systems(Startup, spawn_action_bar_widget)
+            .add_systems(Update, update_ability_icons);
     }
 }
 
@@ -19,6 +24,10 @@ struct ActionBarWidget;
 #[derive(Component)]
 pub struct AbilitySlot(u8);
 
+/// Turns remaining until the ability in the parent [`AbilitySlot`] is ready again.
+#[derive(Component)]
+struct CooldownText;
+
 pub struct ActionBarWidgetConfig {
     pub ability_count: u8,
 }
@@ -55,7 +64,7 @@ impl ActionBarWidgetExt for UiBuilder<'_, UiRoot> {
                 row.style().justify_content(JustifyContent::Center);
 
                 for i in 0..config.ability_count {
-                    row.container(NodeBundle::default(), |column| {
+                    row.container(ImageBundle::default(), |column| {
                         let entity = column.id();
                         column
                             .commands()
@@ -67,7 +76,24 @@ impl ActionBarWidgetExt for UiBuilder<'_, UiRoot> {
                             .width(Val::Px(50.0))
                             .height(Val::Px(50.0))
                             .background_color(css::CORAL.into())
-                            .margin(UiRect::all(Val::Px(5.0)));
+                            .margin(UiRect::all(Val::Px(5.0)))
+                            .justify_content(JustifyContent::Center)
+                            .align_items(AlignItems::Center);
+
+                        column.container(
+                            (
+                                TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font_size: 32.0,
+                                        color: Color::WHITE,
+                                        ..default()
+                                    },
+                                ),
+                                CooldownText,
+                            ),
+                            |_| {},
+                        );
                     });
                 }
             },
@@ -80,3 +106,58 @@ fn spawn_action_bar_widget(mut commands: Commands) {
         .ui_builder(UiRoot)
         .action_bar_widget(ActionBarWidgetConfig::default());
 }
+
+#[allow(clippy::type_complexity)]
+fn update_ability_icons(
+    mut commands: Commands,
+    players: Query<
+        (&AbilitySlots, &AbilityCooldowns),
+        (
+            With<PrimaryPlayer>,
+            Or<(Changed<AbilitySlots>, Changed<AbilityCooldowns>)>,
+        ),
+    >,
+    mut ability_slots: Query<(Entity, &AbilitySlot, &mut BackgroundColor)>,
+    mut cooldown_texts: Query<(&Parent, &mut Text), With<CooldownText>>,
+    definitions: Res<Assets<AbilityDefinition>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some((player_slots, cooldowns)) = players.iter().next() else {
+        return;
+    };
+
+    for (entity, slot, mut background) in ability_slots.iter_mut() {
+        let definition = player_slots
+            .get(slot.0 as usize)
+            .and_then(|handle| definitions.get(handle));
+
+        match definition {
+            Some(definition) => {
+                let icon = if cooldowns.is_ready(slot.0 as usize) {
+                    &definition.icon
+                } else {
+                    &definition.icon_on_cooldown
+                };
+                commands
+                    .entity(entity)
+                    .insert(UiImage::new(asset_server.load(icon)));
+                *background = BackgroundColor(Color::NONE);
+            }
+            None => {
+                commands.entity(entity).insert(UiImage::default());
+                *background = BackgroundColor(css::CORAL.into());
+            }
+        }
+    }
+
+    for (parent, mut text) in cooldown_texts.iter_mut() {
+        let Ok((_, slot, _)) = ability_slots.get(parent.get()) else {
+            continue;
+        };
+
+        text.sections[0].value = match cooldowns.remaining(slot.0 as usize) {
+            0 => String::new(),
+            remaining => remaining.to_string(),
+        };
+    }
+}
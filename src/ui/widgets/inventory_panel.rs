@@ -0,0 +1,261 @@
+use bevy::{color::palettes::css, prelude::*};
+use leafwing_input_manager::prelude::ActionState;
+use sickle_ui::{
+    prelude::*,
+    ui_builder::{UiBuilder, UiBuilderExt, UiRoot},
+};
+
+use crate::{
+    combat::stats::Stats,
+    get_single,
+    input::PlayerInputAction,
+    item::{DropItem, Equipment, EquipmentSlot, Inventory, Item, UseItem},
+    player::PrimaryPlayer,
+    ui::widgets::widget_text,
+    AppState,
+};
+
+pub struct InventoryPanelPlugin;
+
+impl Plugin for InventoryPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_inventory_panel).add_systems(
+            Update,
+            (
+                toggle_inventory_panel,
+                press_inventory_buttons,
+                update_inventory_panel,
+            )
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+const BUTTON_COLOR: Srgba = css::DARK_SLATE_GRAY;
+
+/// Items of the [`PrimaryPlayer`], each with buttons to use, drop or inspect it, above what they
+/// wear and their stats. Opened and closed with [`PlayerInputAction::Inventory`], the buttons are
+/// clicked with the mouse.
+#[derive(Component, Default)]
+struct InventoryPanel {
+    // slot whose description is shown below the items
+    inspected: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InventoryAction {
+    Use,
+    Drop,
+    Inspect,
+}
+
+impl InventoryAction {
+    const ALL: [Self; 3] = [
+        InventoryAction::Use,
+        InventoryAction::Drop,
+        InventoryAction::Inspect,
+    ];
+
+    fn label(self, item: Item) -> &'static str {
+        match self {
+            InventoryAction::Use if item.equipment_slot().is_some() => "Equip",
+            InventoryAction::Use => "Use",
+            InventoryAction::Drop => "Drop",
+            InventoryAction::Inspect => "Inspect",
+        }
+    }
+}
+
+#[derive(Component)]
+struct InventoryButton {
+    slot: usize,
+    action: InventoryAction,
+}
+
+pub trait InventoryPanelExt {
+    fn inventory_panel(&mut self) -> UiBuilder<Entity>;
+}
+
+impl InventoryPanelExt for UiBuilder<'_, UiRoot> {
+    fn inventory_panel(&mut self) -> UiBuilder<Entity> {
+        self.container(
+            (
+                NodeBundle {
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                // keeps clicks on the panel from walking the player there
+                Interaction::default(),
+                InventoryPanel::default(),
+            ),
+            |panel| {
+                let entity = panel.id();
+                panel
+                    .commands()
+                    .entity(entity)
+                    .insert(Name::new("InventoryPanel"));
+
+                panel
+                    .style()
+                    .position_type(PositionType::Absolute)
+                    .right(Val::Percent(5.0))
+                    .top(Val::Percent(10.0))
+                    .width(Val::Px(360.0))
+                    .flex_direction(FlexDirection::Column)
+                    .padding(UiRect::all(Val::Px(10.0)))
+                    .background_color(Color::BLACK.with_alpha(0.8));
+            },
+        )
+    }
+}
+
+fn spawn_inventory_panel(mut commands: Commands) {
+    commands.ui_builder(UiRoot).inventory_panel();
+}
+
+fn toggle_inventory_panel(
+    players: Query<&ActionState<PlayerInputAction>, With<PrimaryPlayer>>,
+    mut panels: Query<(&mut Visibility, &mut InventoryPanel)>,
+) {
+    let action_state = get_single!(players);
+    if !action_state.just_pressed(&PlayerInputAction::Inventory) {
+        return;
+    }
+
+    for (mut visibility, mut panel) in panels.iter_mut() {
+        *visibility = if *visibility == Visibility::Hidden {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        panel.inspected = None;
+    }
+}
+
+fn press_inventory_buttons(
+    players: Query<Entity, With<PrimaryPlayer>>,
+    buttons: Query<(&Interaction, &InventoryButton), Changed<Interaction>>,
+    mut panels: Query<&mut InventoryPanel>,
+    mut use_item_ew: EventWriter<UseItem>,
+    mut drop_item_ew: EventWriter<DropItem>,
+) {
+    let player = get_single!(players);
+
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let slot = button.slot;
+        let inspected = match button.action {
+            InventoryAction::Use => {
+                use_item_ew.send(UseItem { player, slot });
+                None
+            }
+            InventoryAction::Drop => {
+                drop_item_ew.send(DropItem { player, slot });
+                None
+            }
+            InventoryAction::Inspect => Some(slot),
+        };
+
+        // the items after a used or dropped one move up, the inspected slot would be off
+        for mut panel in panels.iter_mut() {
+            panel.inspected = inspected;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_inventory_panel(
+    mut commands: Commands,
+    players: Query<(Ref<Inventory>, Ref<Equipment>, Ref<Stats>), With<PrimaryPlayer>>,
+    panels: Query<(Entity, Ref<InventoryPanel>)>,
+) {
+    let (inventory, equipment, stats) = get_single!(players);
+    let player_changed = inventory.is_changed() || equipment.is_changed() || stats.is_changed();
+
+    for (entity, panel) in panels.iter() {
+        if !player_changed && !panel.is_changed() {
+            continue;
+        }
+
+        commands.entity(entity).despawn_descendants();
+        let mut builder = commands.ui_builder(entity);
+
+        builder.container(
+            widget_text(
+                &format!(
+                    "Inventory {}/{}",
+                    inventory.items().len(),
+                    inventory.capacity
+                ),
+                24.0,
+            ),
+            |_| {},
+        );
+
+        if inventory.items().is_empty() {
+            builder.container(widget_text("Nothing picked up yet", 18.0), |_| {});
+        }
+
+        for (slot, item) in inventory.items().iter().enumerate() {
+            builder.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .margin(UiRect::top(Val::Px(5.0)));
+
+                row.container(widget_text(item.identifier(), 20.0), |label| {
+                    label.style().width(Val::Px(100.0));
+                });
+
+                for action in InventoryAction::ALL {
+                    row.container(
+                        (ButtonBundle::default(), InventoryButton { slot, action }),
+                        |button| {
+                            button
+                                .style()
+                                .margin(UiRect::left(Val::Px(5.0)))
+                                .padding(UiRect::all(Val::Px(5.0)))
+                                .background_color(BUTTON_COLOR.into());
+
+                            button.container(widget_text(action.label(*item), 18.0), |_| {});
+                        },
+                    );
+                }
+            });
+        }
+
+        if let Some(item) = panel.inspected.and_then(|slot| inventory.get(slot)) {
+            builder.container(widget_text(&item.description(), 18.0), |description| {
+                description.style().margin(UiRect::top(Val::Px(10.0)));
+            });
+        }
+
+        builder.container(widget_text("Equipment", 24.0), |heading| {
+            heading.style().margin(UiRect::top(Val::Px(10.0)));
+        });
+        for (slot, name) in [
+            (EquipmentSlot::Weapon, "Weapon"),
+            (EquipmentSlot::Armor, "Armor"),
+            (EquipmentSlot::Trinket, "Trinket"),
+        ] {
+            let worn = equipment.get(slot).map_or("-", |item| item.identifier());
+            builder.container(widget_text(&format!("{name}: {worn}"), 18.0), |_| {});
+        }
+
+        builder.container(
+            widget_text(
+                &format!(
+                    "Attack {}, defense {}, spell power {}, max health {}",
+                    stats.attack, stats.defense, stats.spell_power, stats.max_health
+                ),
+                18.0,
+            ),
+            |text| {
+                text.style().margin(UiRect::top(Val::Px(10.0)));
+            },
+        );
+    }
+}
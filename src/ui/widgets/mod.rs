@@ -1,2 +1,17 @@
 pub mod action_bar;
+pub mod inventory_panel;
 pub mod player_widget;
+
+use bevy::prelude::*;
+
+/// White text for the HUD and the screens on top of the level.
+pub fn widget_text(text: &str, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size,
+            color: Color::WHITE,
+            ..default()
+        },
+    )
+}
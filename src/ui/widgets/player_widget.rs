@@ -4,19 +4,59 @@ use sickle_ui::{
     ui_builder::{UiBuilder, UiBuilderExt, UiRoot},
 };
 
-use crate::ui::PlayerHud;
+use crate::{
+    combat::status::StatusEffects,
+    get_single,
+    player::{Player, PlayerId, PrimaryPlayer},
+    progression::{Experience, LeveledUp},
+    ui::{widgets::widget_text, PlayerHud},
+    AppState,
+};
 
 pub(crate) struct PlayerWidgetPlugin;
 
 impl Plugin for PlayerWidgetPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_player_widget);
+        app.add_systems(Startup, spawn_player_widget).add_systems(
+            Update,
+            (
+                update_experience,
+                show_level_up,
+                hide_level_up,
+                update_status_effects,
+            )
+                .run_if(in_state(AppState::InGame)),
+        );
     }
 }
 
+// time the level up notice stays on screen
+const LEVEL_UP_NOTICE_DURATION: f32 = 3.0;
+
+/// Level and experience of the [`PrimaryPlayer`], with a notice whenever they level up, and the
+/// status effects of every player.
 #[derive(Component)]
 struct PlayerWidget;
 
+#[derive(Component)]
+struct LevelText;
+
+/// Filled up with the experience collected towards the next level.
+#[derive(Component)]
+struct ExperienceBar;
+
+#[derive(Component)]
+struct ExperienceText;
+
+#[derive(Component)]
+struct LevelUpNotice(Timer);
+
+/// Holds an icon for each of the status effects on the players.
+#[derive(Component)]
+struct StatusEffectsRow;
+
+const STATUS_ICON_SIZE: f32 = 12.0;
+
 pub trait PlayerWidgetExt {
     fn player_widget(&mut self) -> UiBuilder<Entity>;
 }
@@ -37,9 +77,53 @@ impl PlayerWidgetExt for UiBuilder<'_, UiRoot> {
                     .position_type(PositionType::Absolute)
                     .left(Val::Percent(10.0))
                     .top(Val::Percent(70.0))
-                    .background_color(css::GREEN.into())
+                    .flex_direction(FlexDirection::Column)
+                    .padding(UiRect::all(Val::Px(10.0)))
+                    .background_color(Color::BLACK.with_alpha(0.6))
                     .width(Val::Percent(20.0))
                     .height(Val::Percent(20.0));
+
+                player_widget.container((widget_text("", 24.0), LevelText), |_| {});
+
+                player_widget.container(NodeBundle::default(), |background| {
+                    background
+                        .style()
+                        .width(Val::Percent(100.0))
+                        .height(Val::Px(10.0))
+                        .margin(UiRect::vertical(Val::Px(5.0)))
+                        .background_color(css::DARK_SLATE_GRAY.into());
+
+                    background.container((NodeBundle::default(), ExperienceBar), |bar| {
+                        bar.style()
+                            .width(Val::Percent(0.0))
+                            .height(Val::Percent(100.0))
+                            .background_color(css::GOLD.into());
+                    });
+                });
+
+                player_widget.container((widget_text("", 16.0), ExperienceText), |_| {});
+
+                player_widget.container((NodeBundle::default(), StatusEffectsRow), |row| {
+                    row.style()
+                        .flex_wrap(FlexWrap::Wrap)
+                        .margin(UiRect::top(Val::Px(5.0)));
+                });
+
+                player_widget.container(
+                    (
+                        TextBundle {
+                            visibility: Visibility::Hidden,
+                            ..widget_text("", 20.0)
+                        },
+                        LevelUpNotice(Timer::from_seconds(
+                            LEVEL_UP_NOTICE_DURATION,
+                            TimerMode::Once,
+                        )),
+                    ),
+                    |notice| {
+                        notice.style().margin(UiRect::top(Val::Px(5.0)));
+                    },
+                );
             },
         )
     }
@@ -48,3 +132,101 @@ impl PlayerWidgetExt for UiBuilder<'_, UiRoot> {
 fn spawn_player_widget(mut commands: Commands) {
     commands.ui_builder(UiRoot).player_widget();
 }
+
+#[allow(clippy::type_complexity)]
+fn update_experience(
+    players: Query<&Experience, (With<PrimaryPlayer>, Changed<Experience>)>,
+    mut level_texts: Query<&mut Text, (With<LevelText>, Without<ExperienceText>)>,
+    mut experience_texts: Query<&mut Text, (With<ExperienceText>, Without<LevelText>)>,
+    mut bars: Query<&mut Style, With<ExperienceBar>>,
+) {
+    let experience = get_single!(players);
+
+    for mut text in level_texts.iter_mut() {
+        text.sections[0].value = format!("Level {}", experience.level);
+    }
+    for mut text in experience_texts.iter_mut() {
+        text.sections[0].value = format!("{} / {} XP", experience.points, experience.next_level());
+    }
+    for mut style in bars.iter_mut() {
+        style.width =
+            Val::Percent(100.0 * experience.points as f32 / experience.next_level() as f32);
+    }
+}
+
+fn show_level_up(
+    mut leveled_up_er: EventReader<LeveledUp>,
+    players: Query<(), With<PrimaryPlayer>>,
+    mut notices: Query<(&mut Text, &mut Visibility, &mut LevelUpNotice)>,
+) {
+    for leveled_up in leveled_up_er.read() {
+        if !players.contains(leveled_up.player) {
+            continue;
+        }
+
+        for (mut text, mut visibility, mut notice) in notices.iter_mut() {
+            text.sections[0].value = format!("Level up! Reached level {}", leveled_up.level);
+            *visibility = Visibility::Inherited;
+            notice.0.reset();
+        }
+    }
+}
+
+fn hide_level_up(mut notices: Query<(&mut Visibility, &mut LevelUpNotice)>, time: Res<Time>) {
+    for (mut visibility, mut notice) in notices.iter_mut() {
+        if *visibility != Visibility::Hidden && notice.0.tick(time.delta()).just_finished() {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+// co-op players share the HUD, so it lists the effects of all of them
+fn update_status_effects(
+    mut commands: Commands,
+    players: Query<(&PlayerId, Ref<StatusEffects>), With<Player>>,
+    rows: Query<Entity, With<StatusEffectsRow>>,
+) {
+    if !players
+        .iter()
+        .any(|(_, status_effects)| status_effects.is_changed())
+    {
+        return;
+    }
+
+    let mut players: Vec<_> = players.iter().collect();
+    players.sort_by_key(|(id, _)| id.0);
+    let co_op = players.len() > 1;
+
+    for entity in rows.iter() {
+        commands.entity(entity).despawn_descendants();
+        let mut builder = commands.ui_builder(entity);
+
+        let effects = players.iter().flat_map(|(id, status_effects)| {
+            status_effects.iter().map(move |effect| (*id, effect))
+        });
+        for (id, effect) in effects {
+            let mut label = match effect.stacks {
+                1 => format!("{} {}", effect.kind.name(), effect.turns_left),
+                stacks => format!("{} x{stacks} {}", effect.kind.name(), effect.turns_left),
+            };
+            if co_op {
+                label = format!("P{} {label}", id.0 + 1);
+            }
+
+            builder.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .margin(UiRect::right(Val::Px(10.0)));
+
+                row.container(NodeBundle::default(), |icon| {
+                    icon.style()
+                        .width(Val::Px(STATUS_ICON_SIZE))
+                        .height(Val::Px(STATUS_ICON_SIZE))
+                        .margin(UiRect::right(Val::Px(4.0)))
+                        .background_color(effect.kind.color().into());
+                });
+                row.container(widget_text(&label, 16.0), |_| {});
+            });
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::GridCoords;
+use game::{
+    enemy::Enemy,
+    events::TurnEnded,
+    headless::{HeadlessAppExt, HeadlessGamePlugin},
+    ldtk::LevelWalls,
+    player::Player,
+    turn::TurnQueue,
+};
+
+// any seed does, fixing one keeps failures reproducible
+const SEED: u64 = 7;
+
+/// Every actor that ended a turn, in order.
+#[derive(Resource, Default)]
+struct EndedTurns(Vec<Entity>);
+
+fn record_ended_turns(mut turn_ended_er: EventReader<TurnEnded>, mut ended: ResMut<EndedTurns>) {
+    ended
+        .0
+        .extend(turn_ended_er.read().map(|TurnEnded(entity)| *entity));
+}
+
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(HeadlessGamePlugin {
+        seed: Some(SEED),
+        ..default()
+    })
+    .init_resource::<EndedTurns>()
+    .add_systems(Update, record_ended_turns);
+    app
+}
+
+fn player(app: &mut App) -> (Entity, GridCoords) {
+    let mut players = app
+        .world_mut()
+        .query_filtered::<(Entity, &GridCoords), With<Player>>();
+    let (entity, coords) = players.single(app.world());
+    (entity, *coords)
+}
+
+// a step the walls don't block
+fn free_direction(app: &App, from: GridCoords) -> GridCoords {
+    let level_walls = app.world().resource::<LevelWalls>();
+    (-1..=1)
+        .flat_map(|x| (-1..=1).map(move |y| GridCoords::new(x, y)))
+        .filter(|direction| *direction != GridCoords::new(0, 0))
+        .find(|direction| {
+            !level_walls.in_wall(&(from + *direction))
+                && !level_walls.cuts_corner(&from, *direction)
+        })
+        .expect("the player is not walled in")
+}
+
+#[test]
+fn player_move_advances_the_turn() {
+    let mut app = headless_app();
+    app.wait_for_player_turn();
+
+    let (player_entity, start) = player(&mut app);
+    let direction = free_direction(&app, start);
+    let now = app.world().resource::<TurnQueue>().now();
+
+    app.step_turn(direction);
+
+    let (_, coords) = player(&mut app);
+    assert_eq!(coords, start + direction);
+    assert!(app.world().resource::<TurnQueue>().now() > now);
+
+    app.wait_for_player_turn();
+    assert!(app
+        .world()
+        .resource::<TurnQueue>()
+        .is_turn_of(player_entity));
+}
+
+#[test]
+fn enemy_acts_after_the_player() {
+    let mut app = headless_app();
+    app.wait_for_player_turn();
+
+    let (player_entity, start) = player(&mut app);
+    let direction = free_direction(&app, start);
+    app.world_mut().resource_mut::<EndedTurns>().0.clear();
+
+    app.step_turn(direction);
+    app.wait_for_player_turn();
+
+    let mut enemies = app.world_mut().query_filtered::<Entity, With<Enemy>>();
+    let enemy = enemies
+        .iter(app.world())
+        .next()
+        .expect("the first level places a slime");
+
+    let ended = &app.world().resource::<EndedTurns>().0;
+    let player_turn = ended
+        .iter()
+        .position(|entity| *entity == player_entity)
+        .expect("the player ended its turn");
+    let enemy_turn = ended
+        .iter()
+        .position(|entity| *entity == enemy)
+        .expect("the slime took its turn");
+    assert!(player_turn < enemy_turn);
+}